@@ -0,0 +1,454 @@
+use std::fmt::{self, Display};
+use std::slice;
+
+use error::{ErrorKind, ParserErrorRef};
+use media_range::MediaRange;
+use media_type::{AnyMediaType, MediaType, Params};
+use parse::{Spec, ParamIndices, parse_range_at, find_unquoted};
+
+const ELEMENT_SEP: u8 = b',';
+const QUALITY_PARAM: &str = "q";
+
+/// A quality value (`q=`) in thousandths, i.e. `q=0.5` is `Quality(500)`
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Quality(u16);
+
+impl Quality {
+
+    /// the quality of elements without `q` parameter (`q=1`)
+    pub const MAX: Quality = Quality(1000);
+
+    /// the quality of not acceptable elements (`q=0`)
+    pub const MIN: Quality = Quality(0);
+
+    /// creates a new quality, returns `None` if `thousandths` is larger then 1000
+    pub fn new(thousandths: u16) -> Option<Quality> {
+        if thousandths <= 1000 {
+            Some(Quality(thousandths))
+        } else {
+            None
+        }
+    }
+
+    pub fn thousandths(self) -> u16 {
+        self.0
+    }
+
+    /// parses a qvalue as defined in RFC 9110 section 12.4.2
+    ///
+    /// I.e. `0` or `1` optionally followed by a `.` and up to three digits,
+    /// where only `0` digits are allowed after `1.`.
+    fn parse(qvalue: &str) -> Option<Quality> {
+        let bytes = qvalue.as_bytes();
+        let (first, tail) = bytes.split_first()?;
+        let decimals = match tail.split_first() {
+            None => &[][..],
+            Some((&b'.', decimals)) if decimals.len() <= 3 => decimals,
+            _ => return None
+        };
+        let mut thousandths = match *first {
+            b'0' => 0,
+            b'1' => 1000,
+            _ => return None
+        };
+        let mut factor = 100;
+        for &digit in decimals {
+            if !digit.is_ascii_digit() {
+                return None;
+            }
+            thousandths += factor * (digit - b'0') as u16;
+            factor /= 10;
+        }
+        Quality::new(thousandths)
+    }
+}
+
+impl Display for Quality {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        if self.0 == 1000 {
+            fter.write_str("1")
+        } else if self.0 == 0 {
+            fter.write_str("0")
+        } else {
+            let decimals = format!("{:03}", self.0);
+            write!(fter, "0.{}", decimals.trim_end_matches('0'))
+        }
+    }
+}
+
+/// A element of a `Accept` header, i.e. a media range with quality and extensions
+#[derive(Clone, Debug)]
+pub struct AcceptEntry<S: Spec> {
+    range: MediaRange<S>,
+    quality: Quality,
+    /// the source of the element, extension names are lowercased
+    ext_buffer: String,
+    extensions: Vec<ParamIndices>
+}
+
+impl<S> AcceptEntry<S>
+    where S: Spec
+{
+    /// the media range, it does not contain the `q` parameter or any extension
+    pub fn range(&self) -> &MediaRange<S> {
+        &self.range
+    }
+
+    /// the quality (`q` parameter), `Quality::MAX` if it was not specified
+    pub fn quality(&self) -> Quality {
+        self.quality
+    }
+
+    /// the accept extensions, i.e. all parameters after the `q` parameter
+    pub fn extensions(&self) -> Params {
+        Params::new(&self.ext_buffer, &self.extensions)
+    }
+}
+
+/// A parsed http `Accept` header
+///
+/// The entries are sorted by quality and then by the specificity of their
+/// media range (see `Accept::parse`).
+#[derive(Clone, Debug)]
+pub struct Accept<S: Spec> {
+    entries: Vec<AcceptEntry<S>>
+}
+
+impl<S> Accept<S>
+    where S: Spec
+{
+    /// parses the value of a `Accept` header
+    ///
+    /// The header is split into elements at all commas which are not in a quoted
+    /// string, empty elements are ignored. Each element is parsed as a media range
+    /// with spec `S`, the first `q` parameter of it is the quality of the element,
+    /// parameters before it belong to the media range and parameters after it are
+    /// accept extensions.
+    ///
+    /// The returned entries are stable sorted by quality and then by specificity
+    /// (`text/plain;format=flowed` > `text/plain` > `text/*` > `*/*`), both in
+    /// descending order. Entries which compare equal keep the order they had in
+    /// the header.
+    ///
+    /// # Error
+    ///
+    /// An error is returned if any element is not a valid media range or if its
+    /// quality is not a valid qvalue (`0` to `1` with up to three decimals).
+    pub fn parse(header: &str) -> Result<Self, ParserErrorRef> {
+        let mut entries = Vec::new();
+        let mut start = 0;
+        while start < header.len() {
+            let end = find_unquoted(header, start, ELEMENT_SEP).unwrap_or(header.len());
+            let element = &header[..end];
+            let range_start = at_pos!(start do S::parse_space | element);
+            if range_start < end {
+                entries.push(parse_entry::<S>(element, range_start)?);
+            }
+            start = end + 1;
+        }
+        entries.sort_by(|left, right| {
+            right.quality.cmp(&left.quality)
+                .then_with(|| right.range.specificity().cmp(&left.range.specificity()))
+        });
+        Ok(Accept { entries })
+    }
+
+    /// returns the quality with which the media type is accepted
+    ///
+    /// The quality is taken from the most specific media range matching the
+    /// media type (see `MediaRange::matches`), e.g. for `text/*;q=0.5, text/plain`
+    /// `text/plain` has quality 1 and `text/html` quality 0.5.
+    ///
+    /// If no range matches `None` is returned.
+    pub fn quality_of(&self, media_type: &AnyMediaType) -> Option<Quality> {
+        let mut best: Option<&AcceptEntry<S>> = None;
+        for entry in self.entries.iter() {
+            if !entry.range.matches(media_type) {
+                continue;
+            }
+            let is_more_specific = best
+                .map(|best| entry.range.specificity() > best.range.specificity())
+                .unwrap_or(true);
+            if is_more_specific {
+                best = Some(entry);
+            }
+        }
+        best.map(|entry| entry.quality)
+    }
+
+    pub fn entries(&self) -> &[AcceptEntry<S>] {
+        &self.entries
+    }
+
+    pub fn iter(&self) -> slice::Iter<AcceptEntry<S>> {
+        self.entries.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl<'a, S> IntoIterator for &'a Accept<S>
+    where S: Spec
+{
+    type Item = &'a AcceptEntry<S>;
+    type IntoIter = slice::Iter<'a, AcceptEntry<S>>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// returns the offered media type which is the best match for the `Accept` header
+///
+/// The quality of each offer is determined with `Accept::quality_of`, offers without
+/// a matching range or with quality `0` are not acceptable. From the remaining offers
+/// the one with the highest quality is returned, if multiple offers have the same
+/// quality the first of them is returned.
+///
+/// If no offer is acceptable `None` is returned.
+pub fn negotiate<'o, S1, S2>(accept: &Accept<S1>, offers: &'o [MediaType<S2>]) -> Option<&'o MediaType<S2>>
+    where S1: Spec, S2: Spec
+{
+    let mut best: Option<(Quality, &'o MediaType<S2>)> = None;
+    for offer in offers {
+        let quality = match accept.quality_of(offer) {
+            Some(quality) if quality > Quality::MIN => quality,
+            _ => continue
+        };
+        let is_better = best
+            .map(|(best_quality, _)| quality > best_quality)
+            .unwrap_or(true);
+        if is_better {
+            best = Some((quality, offer));
+        }
+    }
+    best.map(|(_, offer)| offer)
+}
+
+/// parses the element ending at `element.len()` and starting at `start`
+fn parse_entry<S: Spec>(element: &str, start: usize) -> Result<AcceptEntry<S>, ParserErrorRef> {
+    let mut pres = parse_range_at::<S>(element, start)?;
+    let q_idx = pres.params.iter()
+        .position(|indices| element[indices.start..indices.eq_idx].eq_ignore_ascii_case(QUALITY_PARAM));
+
+    let mut quality = Quality::MAX;
+    let mut extensions = Vec::new();
+    if let Some(q_idx) = q_idx {
+        extensions = pres.params.split_off(q_idx + 1);
+        let q_indices = pres.params.pop().expect("[BUG] q param has to exist");
+        let value_start = q_indices.eq_idx + 1;
+        quality = Quality::parse(&element[value_start..q_indices.end])
+            .ok_or_else(|| ErrorKind::InvalidQValue { pos: value_start }.with_input(element))?;
+    }
+
+    let mut ext_buffer = String::from(&element[start..]);
+    for indices in extensions.iter_mut() {
+        indices.start -= start;
+        indices.eq_idx -= start;
+        indices.end -= start;
+        ext_buffer[indices.start..indices.eq_idx].make_ascii_lowercase();
+    }
+
+    Ok(AcceptEntry {
+        range: pres.rebase(start).into(),
+        quality,
+        ext_buffer,
+        extensions
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use error::ErrorKind;
+    use media_type::MediaType;
+    use spec::{HttpSpec, Modern};
+    use super::{Accept, Quality, negotiate};
+
+    type HttpAccept = Accept<HttpSpec<Modern>>;
+
+    fn offers(offers: &[&str]) -> Vec<MediaType<HttpSpec<Modern>>> {
+        offers.iter()
+            .map(|offer| assert_ok!(MediaType::parse(offer)))
+            .collect()
+    }
+
+    fn best(accept: &str, offered: &[&str]) -> Option<String> {
+        let accept = assert_ok!(HttpAccept::parse(accept));
+        let offered = offers(offered);
+        negotiate(&accept, &offered).map(|offer| offer.to_string())
+    }
+
+    fn ranges(accept: &HttpAccept) -> Vec<String> {
+        accept.iter().map(|entry| entry.range().to_string()).collect()
+    }
+
+    #[test]
+    fn parse_quality() {
+        assert_eq!(Quality::parse("0"), Some(Quality(0)));
+        assert_eq!(Quality::parse("1"), Some(Quality(1000)));
+        assert_eq!(Quality::parse("0.5"), Some(Quality(500)));
+        assert_eq!(Quality::parse("0.05"), Some(Quality(50)));
+        assert_eq!(Quality::parse("0.123"), Some(Quality(123)));
+        assert_eq!(Quality::parse("1.000"), Some(Quality(1000)));
+        assert_eq!(Quality::parse("0."), Some(Quality(0)));
+    }
+
+    #[test]
+    fn parse_invalid_quality() {
+        assert_eq!(Quality::parse(""), None);
+        assert_eq!(Quality::parse("1.001"), None);
+        assert_eq!(Quality::parse("0.1234"), None);
+        assert_eq!(Quality::parse("2"), None);
+        assert_eq!(Quality::parse(".5"), None);
+        assert_eq!(Quality::parse("0,5"), None);
+        assert_eq!(Quality::parse("0.5a"), None);
+    }
+
+    #[test]
+    fn display_quality() {
+        assert_eq!(Quality(1000).to_string(), "1");
+        assert_eq!(Quality(0).to_string(), "0");
+        assert_eq!(Quality(500).to_string(), "0.5");
+        assert_eq!(Quality(5).to_string(), "0.005");
+    }
+
+    #[test]
+    fn parse_simple() {
+        let accept = assert_ok!(HttpAccept::parse("text/html"));
+        assert_eq!(accept.len(), 1);
+        let entry = &accept.entries()[0];
+        assert_eq!(entry.range().to_string(), "text/html");
+        assert_eq!(entry.quality(), Quality::MAX);
+        assert_eq!(entry.extensions().len(), 0);
+    }
+
+    #[test]
+    fn parse_empty() {
+        assert!(assert_ok!(HttpAccept::parse("")).is_empty());
+        assert!(assert_ok!(HttpAccept::parse(" , ,")).is_empty());
+    }
+
+    #[test]
+    fn splits_only_outside_of_quotes() {
+        let accept = assert_ok!(HttpAccept::parse(
+            r#"text/plain; x="a,b\"," , text/html"#
+        ));
+        assert_eq!(ranges(&accept), vec![r#"text/plain; x="a,b\",""#, "text/html"]);
+    }
+
+    #[test]
+    fn quality_is_separated_from_params_and_extensions() {
+        let accept = assert_ok!(HttpAccept::parse("text/plain; format=flowed; Q=0.5; Ext=1; y=z"));
+        let entry = &accept.entries()[0];
+        assert_eq!(entry.range().to_string(), "text/plain; format=flowed");
+        assert_eq!(entry.range().params().len(), 1);
+        assert_eq!(entry.quality(), Quality(500));
+        let extensions = entry.extensions()
+            .map(|(name, value)| (name.as_str(), value.as_str_repr()))
+            .collect::<Vec<_>>();
+        assert_eq!(extensions, vec![("ext", "1"), ("y", "z")]);
+    }
+
+    #[test]
+    fn sorted_by_quality_and_specificity() {
+        let accept = assert_ok!(HttpAccept::parse(
+            "*/*;q=0.1, text/*, text/plain;q=0.5, text/plain;format=flowed, image/png;q=0.5, text/html"
+        ));
+        assert_eq!(ranges(&accept), vec![
+            "text/plain;format=flowed",
+            "text/html",
+            "text/*",
+            "text/plain",
+            "image/png",
+            "*/*"
+        ]);
+    }
+
+    #[test]
+    fn invalid_quality() {
+        let err = HttpAccept::parse("text/html, text/plain;q=1.5").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidQValue { pos: 24 });
+        assert!(HttpAccept::parse("text/plain;q=\"1\"").is_err());
+    }
+
+    #[test]
+    fn invalid_range() {
+        assert!(HttpAccept::parse("text/html, */json").is_err());
+        assert!(HttpAccept::parse("text/html, text").is_err());
+    }
+
+    #[test]
+    fn quality_of_uses_most_specific_range() {
+        let accept = assert_ok!(HttpAccept::parse(
+            "text/*;q=0.3, text/html;q=0.7, text/html;level=1, */*;q=0.5"
+        ));
+        let quality = |media_type: &str| {
+            let media_type = assert_ok!(MediaType::<HttpSpec<Modern>>::parse(media_type));
+            accept.quality_of(&media_type)
+        };
+        assert_eq!(quality("text/html;level=1"), Some(Quality(1000)));
+        assert_eq!(quality("text/html"), Some(Quality(700)));
+        assert_eq!(quality("text/plain"), Some(Quality(300)));
+        assert_eq!(quality("image/jpeg"), Some(Quality(500)));
+        assert_eq!(quality("text/html;level=2"), Some(Quality(700)));
+    }
+
+    #[test]
+    fn quality_of_without_match() {
+        let accept = assert_ok!(HttpAccept::parse("text/*"));
+        let media_type = assert_ok!(MediaType::<HttpSpec<Modern>>::parse("image/png"));
+        assert_eq!(accept.quality_of(&media_type), None);
+    }
+
+    #[test]
+    fn negotiate_picks_highest_quality() {
+        assert_eq!(
+            best("application/json;q=0.5, application/xml", &["application/json", "application/xml"]),
+            Some("application/xml".to_owned())
+        );
+    }
+
+    #[test]
+    fn negotiate_ties_are_broken_by_offer_order() {
+        assert_eq!(
+            best("text/*", &["text/plain", "text/html"]),
+            Some("text/plain".to_owned())
+        );
+        assert_eq!(
+            best("text/*", &["text/html", "text/plain"]),
+            Some("text/html".to_owned())
+        );
+    }
+
+    #[test]
+    fn negotiate_q0_excludes() {
+        assert_eq!(
+            best("*/*, text/html;q=0", &["text/html", "text/plain"]),
+            Some("text/plain".to_owned())
+        );
+        assert_eq!(best("*/*, text/html;q=0", &["text/html"]), None);
+    }
+
+    #[test]
+    fn negotiate_respects_params() {
+        assert_eq!(
+            best("text/plain;charset=utf-8, text/plain;q=0.1", &[
+                "text/plain; charset=latin1",
+                "text/plain; charset=utf-8"
+            ]),
+            Some("text/plain; charset=utf-8".to_owned())
+        );
+    }
+
+    #[test]
+    fn negotiate_without_acceptable_offer() {
+        assert_eq!(best("image/*", &["text/plain"]), None);
+        assert_eq!(best("image/*", &[]), None);
+    }
+}
@@ -0,0 +1,394 @@
+use std::borrow::Cow;
+use std::iter::{Iterator, ExactSizeIterator};
+use std::vec;
+
+use percent_encoding::percent_decode;
+
+use error::{ErrorKind, ParserErrorRef};
+use name::Name;
+use value::Value;
+use parse::ParamIndices;
+use extended_value::ExtendedValue;
+
+const SECTION_SEP: char = '*';
+const EXTENDED_SUFFIX: char = '*';
+
+/// A logical parameter with RFC 2231 continuations and encodings resolved.
+///
+/// For example the parameters `name*0*=utf-8''foo; name*1*=%20bar; name*2=baz.pdf`
+/// are represented by exactly one `DecodedParam` with the name `name` and
+/// the (extended) value `foo barbaz.pdf`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecodedParam<'a> {
+    name: Name<'a>,
+    value: DecodedValue<'a>
+}
+
+impl<'a> DecodedParam<'a> {
+
+    /// the name of the parameter without any section number or `*` suffix
+    pub fn name(&self) -> Name<'a> {
+        self.name
+    }
+
+    pub fn value(&self) -> &DecodedValue<'a> {
+        &self.value
+    }
+
+    pub fn into_value(self) -> DecodedValue<'a> {
+        self.value
+    }
+}
+
+/// The value of a `DecodedParam`
+#[derive(Debug, Clone, PartialEq)]
+pub enum DecodedValue<'a> {
+    /// the (unquoted) value of a parameter of which no section was percent encoded
+    Plain(Cow<'a, str>),
+
+    /// the percent-decoded value of a parameter of which at last one section
+    /// was percent encoded
+    ///
+    /// The charset and language are taken from the first section.
+    Extended(ExtendedValue<'a>)
+}
+
+
+/// Iterator over the decoded parameters of a media type.
+///
+/// Parameters are yielded in the order in which their first section
+/// appears in the media type.
+#[derive(Debug, Clone)]
+pub struct DecodedParams<'a> {
+    iter: vec::IntoIter<DecodedParam<'a>>
+}
+
+impl<'a> DecodedParams<'a> {
+
+    /// decodes all parameters described by `params` in `source`
+    ///
+    /// # Error
+    ///
+    /// An error is returned if the sections of a continued parameter are
+    /// out of order, duplicated or missing, or if the first section of an
+    /// extended value does not contain a `charset'language'` prefix.
+    pub(crate) fn new(source: &'a str, params: &[ParamIndices])
+        -> Result<Self, ParserErrorRef<'a>>
+    {
+        let mut groups: Vec<(&'a str, Vec<Section<'a>>)> = Vec::new();
+        for indices in params.iter() {
+            let raw_name = &source[indices.start..indices.eq_idx];
+            let (base, number, extended) = split_section_name(raw_name);
+            let section = Section {
+                pos: indices.start,
+                number, extended,
+                value: Value::new_unchecked(&source[indices.eq_idx+1..indices.end])
+            };
+            if let Some(&mut (_, ref mut sections)) = groups.iter_mut().find(|g| g.0 == base) {
+                sections.push(section);
+                continue;
+            }
+            groups.push((base, vec![section]));
+        }
+
+        let mut out = Vec::with_capacity(groups.len());
+        for (base, sections) in groups {
+            check_sections(source, &sections)?;
+            let value = decode_sections(source, &sections)?;
+            out.push(DecodedParam { name: Name::new_unchecked(base), value });
+        }
+
+        Ok(DecodedParams { iter: out.into_iter() })
+    }
+}
+
+impl<'a> Iterator for DecodedParams<'a> {
+    type Item = DecodedParam<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next()
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a> ExactSizeIterator for DecodedParams<'a> {
+    #[inline]
+    fn len(&self) -> usize {
+        self.iter.len()
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Section<'a> {
+    pos: usize,
+    number: Option<usize>,
+    extended: bool,
+    value: Value<'a>
+}
+
+/// splits a raw parameter name into its base name, section number and if it's extended
+///
+/// e.g. `"name*1*"` is split into `("name", Some(1), true)`. Section numbers with
+/// leading zeros are not valid given RFC 2231 so `"name*01"` is seen as a normal
+/// parameter with the name `"name*01"`.
+pub(crate) fn split_section_name(name: &str) -> (&str, Option<usize>, bool) {
+    let mut base = name;
+    let mut extended = false;
+    if base.len() > 1 && base.ends_with(EXTENDED_SUFFIX) {
+        base = &base[..base.len()-1];
+        extended = true;
+    }
+
+    if let Some(sep_idx) = base.rfind(SECTION_SEP) {
+        let digits = &base[sep_idx+1..];
+        let valid = sep_idx > 0
+            && !digits.is_empty()
+            && digits.bytes().all(|bch| bch.is_ascii_digit())
+            && (digits == "0" || !digits.starts_with('0'));
+        if valid {
+            if let Ok(number) = digits.parse() {
+                return (&base[..sep_idx], Some(number), extended);
+            }
+        }
+    }
+    (base, None, extended)
+}
+
+fn check_sections<'a>(source: &'a str, sections: &[Section]) -> Result<(), ParserErrorRef<'a>> {
+    let first_pos = sections[0].pos;
+    if sections.len() == 1 {
+        return match sections[0].number {
+            None | Some(0) => Ok(()),
+            Some(_) => Err(ErrorKind::MissingParamSection {
+                pos: first_pos, section: 0
+            }.with_input(source))
+        };
+    }
+
+    for (idx, section) in sections.iter().enumerate() {
+        let number = match section.number {
+            Some(number) => number,
+            // a section-less parameter can not be combined with any other section
+            None => {
+                let pos = if idx == 0 { sections[1].pos } else { section.pos };
+                return Err(ErrorKind::DuplicateParamSection { pos }.with_input(source));
+            }
+        };
+        if sections[..idx].iter().any(|prev| prev.number == Some(number)) {
+            return Err(ErrorKind::DuplicateParamSection { pos: section.pos }.with_input(source));
+        }
+    }
+
+    // no duplicates so there is a gap iff one of the numbers is >= len
+    for expected in 0..sections.len() {
+        if !sections.iter().any(|section| section.number == Some(expected)) {
+            return Err(ErrorKind::MissingParamSection {
+                pos: first_pos, section: expected
+            }.with_input(source));
+        }
+    }
+
+    for (idx, section) in sections.iter().enumerate() {
+        if section.number != Some(idx) {
+            return Err(ErrorKind::UnorderedParamSection { pos: section.pos }.with_input(source));
+        }
+    }
+
+    Ok(())
+}
+
+/// decodes sections which are already known to be complete and in order
+fn decode_sections<'a>(source: &'a str, sections: &[Section<'a>])
+    -> Result<DecodedValue<'a>, ParserErrorRef<'a>>
+{
+    if !sections.iter().any(|section| section.extended) {
+        if sections.len() == 1 {
+            return Ok(DecodedValue::Plain(sections[0].value.to_content()));
+        }
+        let mut value = String::new();
+        for section in sections {
+            value.push_str(&section.value.to_content());
+        }
+        return Ok(DecodedValue::Plain(Cow::Owned(value)));
+    }
+
+    let mut extended = None;
+    let mut bytes = Vec::new();
+    for (idx, section) in sections.iter().enumerate() {
+        if !section.extended {
+            bytes.extend_from_slice(section.value.to_content().as_bytes());
+            continue;
+        }
+        // extended values are percent encoded instead of quoted (RFC 2231)
+        if section.value.is_quoted() {
+            return Err(ErrorKind::MalformedExtendedValue { pos: section.pos }.with_input(source));
+        }
+        let mut encoded = section.value.as_str_repr();
+        if idx == 0 {
+            let (value, tail) = ExtendedValue::split_encoded(encoded)
+                .ok_or_else(|| {
+                    ErrorKind::MalformedExtendedValue { pos: section.pos }.with_input(source)
+                })?;
+            extended = Some(value);
+            encoded = tail;
+        }
+        bytes.extend(percent_decode(encoded.as_bytes()));
+    }
+
+    let value = match extended {
+        Some(value) => value.with_bytes(bytes),
+        // if the first section is not extended no charset/language was given
+        None => ExtendedValue::unspecified(bytes)
+    };
+    Ok(DecodedValue::Extended(value))
+}
+
+
+#[cfg(test)]
+mod test {
+    use std::borrow::Cow;
+
+    use error::ErrorKind;
+    use media_type::MediaType;
+    use spec::AnySpec;
+    use extended_value::ExtendedValue;
+    use super::{DecodedValue, split_section_name};
+
+    fn decode(input: &str) -> Vec<(String, DecodedValue<'static>)> {
+        let mt = assert_ok!(MediaType::<AnySpec>::parse(input));
+        let params = assert_ok!(mt.decoded_params());
+        params
+            .map(|param| {
+                let value = match *param.value() {
+                    DecodedValue::Plain(ref value) =>
+                        DecodedValue::Plain(Cow::Owned(value.to_string())),
+                    DecodedValue::Extended(ref value) =>
+                        DecodedValue::Extended(ExtendedValue::unspecified(value.bytes().to_vec()))
+                };
+                (param.name().as_str().to_owned(), value)
+            })
+            .collect()
+    }
+
+    fn decode_err(input: &str) -> ErrorKind {
+        let mt = assert_ok!(MediaType::<AnySpec>::parse(input));
+        let res = mt.decoded_params().map(|_| ()).map_err(|err| err.kind());
+        res.unwrap_err()
+    }
+
+    #[test]
+    fn split_names() {
+        assert_eq!(split_section_name("name"), ("name", None, false));
+        assert_eq!(split_section_name("name*"), ("name", None, true));
+        assert_eq!(split_section_name("name*0"), ("name", Some(0), false));
+        assert_eq!(split_section_name("name*12*"), ("name", Some(12), true));
+        assert_eq!(split_section_name("name*01"), ("name*01", None, false));
+        assert_eq!(split_section_name("*"), ("*", None, false));
+        assert_eq!(split_section_name("*0"), ("*0", None, false));
+    }
+
+    #[test]
+    fn plain_params_are_unquoted() {
+        assert_eq!(decode("text/plain; a=b; c=\"d e\""), vec![
+            ("a".to_owned(), DecodedValue::Plain(Cow::Borrowed("b"))),
+            ("c".to_owned(), DecodedValue::Plain(Cow::Borrowed("d e")))
+        ]);
+    }
+
+    #[test]
+    fn plain_continuations_are_joined() {
+        assert_eq!(decode("text/plain; name*0=foo; name*1=\" bar\"; x=y"), vec![
+            ("name".to_owned(), DecodedValue::Plain(Cow::Borrowed("foo bar"))),
+            ("x".to_owned(), DecodedValue::Plain(Cow::Borrowed("y")))
+        ]);
+    }
+
+    #[test]
+    fn mixed_continuations_are_decoded() {
+        let mt = assert_ok!(MediaType::<AnySpec>::parse(
+            "application/pdf; name*0*=utf-8''foo; name*1*=%20bar; name*2=baz.pdf"));
+        let mut params = assert_ok!(mt.decoded_params());
+        assert_eq!(params.len(), 1);
+        let param = params.next().unwrap();
+        assert_eq!(param.name(), "name");
+        let value = match param.into_value() {
+            DecodedValue::Extended(value) => value,
+            other => panic!("unexpected value: {:?}", other)
+        };
+        assert_eq!(value.charset(), "utf-8");
+        assert_eq!(value.language(), None);
+        assert_eq!(value.bytes(), b"foo barbaz.pdf");
+    }
+
+    #[test]
+    fn single_extended_value() {
+        let mt = assert_ok!(MediaType::<AnySpec>::parse("text/plain; title*=us-ascii'en'a%2Ab"));
+        let value = assert_ok!(mt.get_decoded_param("title")).unwrap();
+        let expected = assert_ok!(ExtendedValue::new("us-ascii", "en", b"a*b".to_vec()));
+        assert_eq!(value, DecodedValue::Extended(expected));
+    }
+
+    #[test]
+    fn missing_prefix_is_an_error() {
+        assert_eq!(
+            decode_err("text/plain; title*=a%2Ab"),
+            ErrorKind::MalformedExtendedValue { pos: 12 }
+        );
+    }
+
+    #[test]
+    fn quoted_extended_value_is_an_error() {
+        assert_eq!(
+            decode_err("text/plain; a*=\"utf-8''x%20y\""),
+            ErrorKind::MalformedExtendedValue { pos: 12 }
+        );
+        assert_eq!(
+            decode_err("text/plain; a*0*=utf-8''x; a*1*=\"%20y\""),
+            ErrorKind::MalformedExtendedValue { pos: 27 }
+        );
+    }
+
+    #[test]
+    fn missing_first_section() {
+        assert_eq!(
+            decode_err("text/plain; name*1=a"),
+            ErrorKind::MissingParamSection { pos: 12, section: 0 }
+        );
+    }
+
+    #[test]
+    fn missing_section_in_between() {
+        assert_eq!(
+            decode_err("text/plain; name*0=a; name*2=b"),
+            ErrorKind::MissingParamSection { pos: 12, section: 1 }
+        );
+    }
+
+    #[test]
+    fn duplicate_section() {
+        assert_eq!(
+            decode_err("text/plain; name*0=a; name*1=b; name*1=c"),
+            ErrorKind::DuplicateParamSection { pos: 32 }
+        );
+    }
+
+    #[test]
+    fn section_mixed_with_unsectioned_param() {
+        assert_eq!(
+            decode_err("text/plain; name=a; name*0=b"),
+            ErrorKind::DuplicateParamSection { pos: 20 }
+        );
+    }
+
+    #[test]
+    fn unordered_sections() {
+        assert_eq!(
+            decode_err("text/plain; name*1=b; name*0=a"),
+            ErrorKind::UnorderedParamSection { pos: 12 }
+        );
+    }
+}
@@ -1,4 +1,5 @@
 use std::fmt::{self, Display};
+use std::hash::{Hash, Hasher};
 use std::error::Error as StdError;
 use quoted_string::error::CoreError;
 
@@ -19,6 +20,29 @@ impl Display for ExpectedChar {
     }
 }
 
+/// The syntactic part of a media type which was parsed when a error occurred
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Component {
+    Type,
+    Subtype,
+    ParamName,
+    ParamValue,
+    Comment
+}
+
+impl Display for Component {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        use self::Component::*;
+        let name = match *self {
+            Type => "type",
+            Subtype => "subtype",
+            ParamName => "parameter name",
+            ParamValue => "parameter value",
+            Comment => "comment"
+        };
+        fter.write_str(name)
+    }
+}
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub enum ErrorKind {
@@ -38,10 +62,74 @@ pub enum ErrorKind {
         expected: ExpectedChar
     },
 
-    UnexpectedEof,
+    /// the input ended while `expected` was still expected
+    ///
+    /// For unterminated constructs (comments, quoted strings) `pos` points
+    /// to the start of the construct, i.e. the `(` or `"`, else it's the
+    /// position of the eof.
+    UnexpectedEof {
+        pos: usize,
+        expected: ExpectedChar
+    },
 
     IllegalCrNlSeq {
         pos: usize
+    },
+
+    /// a section of a RFC 2231 continued parameter is missing
+    MissingParamSection {
+        pos: usize,
+        section: usize
+    },
+
+    /// a parameter or a section of a RFC 2231 continued parameter appears more than once
+    DuplicateParamSection {
+        pos: usize
+    },
+
+    /// the sections of a RFC 2231 continued parameter are not in ascending order
+    UnorderedParamSection {
+        pos: usize
+    },
+
+    /// the first section of an extended parameter value lacks the `charset'language'` prefix
+    /// or a section of a extended parameter value is quoted
+    MalformedExtendedValue {
+        pos: usize
+    },
+
+    /// the `q` parameter of a `Accept` header element is not a valid
+    /// qvalue (`0`-`1` with at most three decimals)
+    InvalidQValue {
+        pos: usize
+    },
+
+    /// a type/subtype (RFC 6838 restricted-name) doesn't start with a alphanumeric char
+    InvalidNameStart {
+        pos: usize
+    },
+
+    /// a type/subtype (RFC 6838 restricted-name) is longer then 127 chars
+    ///
+    /// `pos` points to the first char exceeding the limit.
+    NameTooLong {
+        pos: usize
+    },
+
+    /// a type/subtype (RFC 6838 restricted-name) contains a token char which
+    /// is not allowed in a restricted-name
+    InvalidRestrictedChar {
+        pos: usize
+    },
+
+    /// the type is not one of the registered top-level types
+    UnknownTopLevelType {
+        pos: usize
+    },
+
+    /// the name of a header field is not the expected one (e.g. `Content-Type`)
+    InvalidHeaderName {
+        pos: usize
     }
 }
 
@@ -51,6 +139,104 @@ impl ErrorKind {
         ParserErrorRef::new(input, self)
     }
 
+    /// the byte position in the input at which the error occurred
+    pub fn pos(&self) -> usize {
+        use self::ErrorKind::*;
+        match *self {
+            QuotedParamValue { pos, .. }
+            | UnquotedParamValue { pos, .. }
+            | UnexpectedChar { pos, .. }
+            | UnexpectedEof { pos, .. }
+            | IllegalCrNlSeq { pos }
+            | MissingParamSection { pos, .. }
+            | DuplicateParamSection { pos }
+            | UnorderedParamSection { pos }
+            | MalformedExtendedValue { pos }
+            | InvalidQValue { pos }
+            | InvalidNameStart { pos }
+            | NameTooLong { pos }
+            | InvalidRestrictedChar { pos }
+            | UnknownTopLevelType { pos }
+            | InvalidHeaderName { pos } => pos
+        }
+    }
+
+    /// returns the same error with it's position moved by `offset`
+    pub(crate) fn shifted(self, offset: usize) -> Self {
+        use self::ErrorKind::*;
+        match self {
+            QuotedParamValue { pos, cause } => QuotedParamValue { pos: pos + offset, cause },
+            UnquotedParamValue { pos, cause } => UnquotedParamValue { pos: pos + offset, cause },
+            UnexpectedChar { pos, expected } => UnexpectedChar { pos: pos + offset, expected },
+            UnexpectedEof { pos, expected } => UnexpectedEof { pos: pos + offset, expected },
+            IllegalCrNlSeq { pos } => IllegalCrNlSeq { pos: pos + offset },
+            MissingParamSection { pos, section } => MissingParamSection { pos: pos + offset, section },
+            DuplicateParamSection { pos } => DuplicateParamSection { pos: pos + offset },
+            UnorderedParamSection { pos } => UnorderedParamSection { pos: pos + offset },
+            MalformedExtendedValue { pos } => MalformedExtendedValue { pos: pos + offset },
+            InvalidQValue { pos } => InvalidQValue { pos: pos + offset },
+            InvalidNameStart { pos } => InvalidNameStart { pos: pos + offset },
+            NameTooLong { pos } => NameTooLong { pos: pos + offset },
+            InvalidRestrictedChar { pos } => InvalidRestrictedChar { pos: pos + offset },
+            UnknownTopLevelType { pos } => UnknownTopLevelType { pos: pos + offset },
+            InvalidHeaderName { pos } => InvalidHeaderName { pos: pos + offset }
+        }
+    }
+
+    /// a stable, machine readable identifier for the kind of error
+    ///
+    /// Unlike the `Display` output the codes won't change between releases
+    /// (except if a variant is removed), making them usable e.g. for metrics
+    /// labels or API error payloads. The codes are:
+    ///
+    /// | variant                  | code                        |
+    /// |--------------------------|-----------------------------|
+    /// | `QuotedParamValue`       | `quoted-param-value`        |
+    /// | `UnquotedParamValue`     | `unquoted-param-value`      |
+    /// | `UnexpectedChar`         | `unexpected-char`           |
+    /// | `UnexpectedEof`          | `unexpected-eof`            |
+    /// | `IllegalCrNlSeq`         | `illegal-crnl-seq`          |
+    /// | `MissingParamSection`    | `missing-param-section`     |
+    /// | `DuplicateParamSection`  | `duplicate-param-section`   |
+    /// | `UnorderedParamSection`  | `unordered-param-section`   |
+    /// | `MalformedExtendedValue` | `malformed-extended-value`  |
+    /// | `InvalidQValue`          | `invalid-q-value`           |
+    /// | `InvalidNameStart`       | `invalid-name-start`        |
+    /// | `NameTooLong`            | `name-too-long`             |
+    /// | `InvalidRestrictedChar`  | `invalid-restricted-char`   |
+    /// | `UnknownTopLevelType`    | `unknown-top-level-type`    |
+    /// | `InvalidHeaderName`      | `invalid-header-name`       |
+    pub fn code(&self) -> &'static str {
+        use self::ErrorKind::*;
+        match *self {
+            QuotedParamValue { .. } => "quoted-param-value",
+            UnquotedParamValue { .. } => "unquoted-param-value",
+            UnexpectedChar { .. } => "unexpected-char",
+            UnexpectedEof { .. } => "unexpected-eof",
+            IllegalCrNlSeq { .. } => "illegal-crnl-seq",
+            MissingParamSection { .. } => "missing-param-section",
+            DuplicateParamSection { .. } => "duplicate-param-section",
+            UnorderedParamSection { .. } => "unordered-param-section",
+            MalformedExtendedValue { .. } => "malformed-extended-value",
+            InvalidQValue { .. } => "invalid-q-value",
+            InvalidNameStart { .. } => "invalid-name-start",
+            NameTooLong { .. } => "name-too-long",
+            InvalidRestrictedChar { .. } => "invalid-restricted-char",
+            UnknownTopLevelType { .. } => "unknown-top-level-type",
+            InvalidHeaderName { .. } => "invalid-header-name"
+        }
+    }
+
+    /// the stable code of the wrapped `CoreError` (see `core_error_code`), if there is one
+    pub fn cause_code(&self) -> Option<&'static str> {
+        use self::ErrorKind::*;
+        match *self {
+            QuotedParamValue { cause, .. }
+            | UnquotedParamValue { cause, .. } => Some(core_error_code(cause)),
+            _ => None
+        }
+    }
+
     fn description(&self) -> &str {
         use self::ErrorKind::*;
         match *self {
@@ -58,7 +244,17 @@ impl ErrorKind {
             UnquotedParamValue {..} => "parsing unquoted parameter value failed",
             UnexpectedChar { .. } => "parsing hit an unexpected character",
             UnexpectedEof { .. } => "parsing unexpectedly hit eof",
-            IllegalCrNlSeq { .. } => r#"parsing found a illegal "\r\n "/"\r\n\t" seqence"#
+            IllegalCrNlSeq { .. } => r#"parsing found a illegal "\r\n "/"\r\n\t" seqence"#,
+            MissingParamSection { .. } => "a section of a continued parameter is missing",
+            DuplicateParamSection { .. } => "a parameter (section) appears more than once",
+            UnorderedParamSection { .. } => "the sections of a continued parameter are out of order",
+            MalformedExtendedValue { .. } => "extended parameter value is missing the charset/language prefix",
+            InvalidQValue { .. } => "invalid quality value",
+            InvalidNameStart { .. } => "type/subtype doesn't start with a alphanumeric char",
+            NameTooLong { .. } => "type/subtype is longer then 127 chars",
+            InvalidRestrictedChar { .. } => "type/subtype contains a char not allowed in a restricted-name",
+            UnknownTopLevelType { .. } => "unknown top-level type",
+            InvalidHeaderName { .. } => "unexpected header name"
         }
     }
 
@@ -95,27 +291,107 @@ impl ErrorKind {
                     one_char_str(input, pos), input, pos, expected
                 )
             },
-            UnexpectedEof => {
-                write!(fter, "hit eof unexpectedly in {:?}", input)
+            UnexpectedEof { pos, expected } => {
+                write!(
+                    fter,
+                    "hit eof unexpectedly while parsing {:?} expected {} (for the part starting at {})",
+                    input, expected, pos
+                )
             },
 
             IllegalCrNlSeq { pos } => {
                 write!(fter, "hit invalid \"\\r\\n \"/\"\\r\\n\\t\" seq in {:?} at {}", input, pos)
+            },
+
+            MissingParamSection { pos, section } => {
+                write!(fter, "section {} of parameter at {} is missing in {:?}", section, pos, input)
+            },
+
+            DuplicateParamSection { pos } => {
+                write!(fter, "duplicate parameter (section) in {:?} at {}", input, pos)
+            },
+
+            UnorderedParamSection { pos } => {
+                write!(fter, "out of order parameter section in {:?} at {}", input, pos)
+            },
+
+            MalformedExtendedValue { pos } => {
+                write!(fter, "extended parameter value without charset/language prefix in {:?} at {}", input, pos)
+            },
+
+            InvalidQValue { pos } => {
+                write!(fter, "invalid quality value in {:?} at {}", input, pos)
+            },
+
+            InvalidNameStart { pos } => {
+                write!(fter, "type/subtype in {:?} at {} doesn't start with a alphanumeric char", input, pos)
+            },
+
+            NameTooLong { pos } => {
+                write!(fter, "type/subtype in {:?} exceeds 127 chars at {}", input, pos)
+            },
+
+            InvalidRestrictedChar { pos } => {
+                write!(
+                    fter, "char {:?} in {:?} at {} is not allowed in a restricted-name",
+                    one_char_str(input, pos), input, pos
+                )
+            },
+
+            UnknownTopLevelType { pos } => {
+                write!(fter, "unknown top-level type in {:?} at {}", input, pos)
+            },
+
+            InvalidHeaderName { pos } => {
+                write!(fter, "unexpected header name in {:?} at {}", input, pos)
             }
         }
     }
 }
 
+/// where in the media type a error occurred
+///
+/// The context is not part of the identity of an error, i.e. errors with the
+/// same input and kind are equal (and have the same hash) independent of it.
+#[derive(Copy, Clone, Debug, Default)]
+struct ErrorContext {
+    component: Option<Component>,
+    /// the index of the parameter (in the order they appear in the input)
+    param_index: Option<usize>,
+    /// the start and end of the parameter name in the input
+    param_name: Option<(usize, usize)>
+}
+
+impl PartialEq for ErrorContext {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for ErrorContext {}
+
+impl Hash for ErrorContext {
+    fn hash<H: Hasher>(&self, _state: &mut H) {}
+}
+
+impl ErrorContext {
+
+    fn param_name<'a>(&self, input: &'a str) -> Option<&'a str> {
+        self.param_name.and_then(|(start, end)| input.get(start..end))
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct ParserErrorRef<'a> {
     input: &'a str,
-    kind: ErrorKind
+    kind: ErrorKind,
+    context: ErrorContext
 }
 
 impl<'a> ParserErrorRef<'a> {
 
     pub fn new(input: &'a str, kind: ErrorKind) -> Self {
-        ParserErrorRef { input, kind }
+        ParserErrorRef { input, kind, context: ErrorContext::default() }
     }
 
     pub fn input(&self) -> &'a str {
@@ -126,8 +402,74 @@ impl<'a> ParserErrorRef<'a> {
         self.kind
     }
 
+    /// the part of the media type which was parsed when the error occurred (if known)
+    pub fn component(&self) -> Option<Component> {
+        self.context.component
+    }
+
+    /// the index of the parameter in which the error occurred
+    ///
+    /// The index is based on the order of the parameters in the input,
+    /// it's `None` if the error did not occur in a parameter.
+    pub fn param_index(&self) -> Option<usize> {
+        self.context.param_index
+    }
+
+    /// the name of the parameter in which the error occurred
+    ///
+    /// It's `None` if the error did not occur in a parameter or if
+    /// it occurred before the name was completely parsed.
+    pub fn param_name(&self) -> Option<&'a str> {
+        self.context.param_name(self.input)
+    }
+
     pub fn to_owned(&self) -> Error {
-        Error::new(self.input, self.kind)
+        Error { input: self.input.to_owned(), kind: self.kind, context: self.context }
+    }
+
+    /// returns a `Display` implementation rendering the input with a caret below the error
+    ///
+    /// E.g. for `text/plain; a b`:
+    ///
+    /// ```text
+    /// text/plain; a b
+    ///               ^ parsing hit an unexpected character (in parameter name of `a`)
+    /// ```
+    pub fn caret_display(&self) -> CaretDisplay<'a> {
+        CaretDisplay {
+            input: self.input,
+            kind: self.kind,
+            component: self.context.component,
+            param_name: self.param_name()
+        }
+    }
+
+    /// sets the component if no (more specific) component was set before
+    pub(crate) fn in_component(mut self, component: Component) -> Self {
+        if self.context.component.is_none() {
+            self.context.component = Some(component);
+        }
+        self
+    }
+
+    /// sets the index of the parameter in which the error occurred
+    pub(crate) fn in_param(mut self, index: usize) -> Self {
+        self.context.param_index = Some(index);
+        self
+    }
+
+    /// sets the position of the name of the parameter in which the error occurred
+    pub(crate) fn with_param_name(mut self, start: usize, end: usize) -> Self {
+        self.context.param_name = Some((start, end));
+        self
+    }
+
+    /// rebases a error of parsing `&input[offset..]` to be a error of parsing `input`
+    pub(crate) fn rebased(self, input: &'a str, offset: usize) -> Self {
+        let mut context = self.context;
+        context.param_name = context.param_name
+            .map(|(start, end)| (start + offset, end + offset));
+        ParserErrorRef { input, kind: self.kind.shifted(offset), context }
     }
 }
 
@@ -158,13 +500,14 @@ impl<'a> From<ParserErrorRef<'a>> for Error {
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Error {
     input: String,
-    kind: ErrorKind
+    kind: ErrorKind,
+    context: ErrorContext
 }
 
 impl Error {
 
     pub fn new<I: Into<String>>(input: I, kind: ErrorKind) -> Self {
-        Error { input: input.into(), kind }
+        Error { input: input.into(), kind, context: ErrorContext::default() }
     }
 
     pub fn input(&self) -> &str {
@@ -180,9 +523,30 @@ impl Error {
     pub fn as_ref(&self) -> ParserErrorRef {
         ParserErrorRef {
             input: self.input.as_ref(),
-            kind: self.kind
+            kind: self.kind,
+            context: self.context
         }
     }
+
+    /// see `ParserErrorRef::component`
+    pub fn component(&self) -> Option<Component> {
+        self.context.component
+    }
+
+    /// see `ParserErrorRef::param_index`
+    pub fn param_index(&self) -> Option<usize> {
+        self.context.param_index
+    }
+
+    /// see `ParserErrorRef::param_name`
+    pub fn param_name(&self) -> Option<&str> {
+        self.context.param_name(&self.input)
+    }
+
+    /// see `ParserErrorRef::caret_display`
+    pub fn caret_display(&self) -> CaretDisplay {
+        self.as_ref().caret_display()
+    }
 }
 
 impl Display for Error {
@@ -201,6 +565,77 @@ impl StdError for Error {
     }
 }
 
+/// Renders a error as the input with a `^` below the position of the error
+///
+/// Line breaks and tabs in the input are escaped (e.g. as `\r\n`) so that
+/// the input is rendered on one line, the caret is placed based on the number
+/// of (escaped) chars before the error position, not the number of bytes.
+#[derive(Copy, Clone, Debug)]
+pub struct CaretDisplay<'a> {
+    input: &'a str,
+    kind: ErrorKind,
+    component: Option<Component>,
+    param_name: Option<&'a str>
+}
+
+impl<'a> Display for CaretDisplay<'a> {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        let pos = self.kind.pos();
+        let mut column = 0;
+        for (idx, ch) in self.input.char_indices() {
+            let escaped = match ch {
+                '\r' => "\\r",
+                '\n' => "\\n",
+                '\t' => "\\t",
+                _ => ""
+            };
+            if escaped.is_empty() {
+                write!(fter, "{}", ch)?;
+            } else {
+                fter.write_str(escaped)?;
+            }
+            if idx < pos {
+                column += if escaped.is_empty() { 1 } else { escaped.len() };
+            }
+        }
+        fter.write_str("\n")?;
+        for _ in 0..column {
+            fter.write_str(" ")?;
+        }
+        write!(fter, "^ {}", self.kind.description())?;
+        match (self.component, self.param_name) {
+            (Some(component), Some(name)) => write!(fter, " (in {} of `{}`)", component, name),
+            (Some(component), None) => write!(fter, " (in {})", component),
+            (None, _) => Ok(())
+        }
+    }
+}
+
+/// a stable, machine readable identifier for a `CoreError` from `quoted_string`
+///
+/// The codes are:
+///
+/// | variant                    | code                          |
+/// |----------------------------|-------------------------------|
+/// | `AdvancedFailedAutomaton`  | `advanced-failed-automaton`   |
+/// | `QuotedStringAlreadyEnded` | `quoted-string-already-ended` |
+/// | `UnquoteableCharQuoted`    | `unquoteable-char-quoted`     |
+/// | `DoesNotStartWithDQuotes`  | `missing-start-dquotes`       |
+/// | `DoesNotEndWithDQuotes`    | `missing-end-dquotes`         |
+/// | `InvalidChar`              | `invalid-char`                |
+/// | `ZeroSizedValue`           | `zero-sized-value`            |
+pub fn core_error_code(err: CoreError) -> &'static str {
+    use self::CoreError::*;
+    match err {
+        AdvancedFailedAutomaton => "advanced-failed-automaton",
+        QuotedStringAlreadyEnded => "quoted-string-already-ended",
+        UnquoteableCharQuoted => "unquoteable-char-quoted",
+        DoesNotStartWithDQuotes => "missing-start-dquotes",
+        DoesNotEndWithDQuotes => "missing-end-dquotes",
+        InvalidChar => "invalid-char",
+        ZeroSizedValue => "zero-sized-value"
+    }
+}
 
 fn one_char_str(inp: &str, offset: usize) -> &str {
     inp.get(offset..)
@@ -211,4 +646,104 @@ fn one_char_str(inp: &str, offset: usize) -> &str {
             &tail[..first_char_len]
         })
         .unwrap_or("<[BUG] invalid str index in error>")
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod test {
+    use super::{Component, Error, ErrorKind, ExpectedChar, ParserErrorRef};
+    use quoted_string::error::CoreError;
+
+    fn unexpected(pos: usize) -> ErrorKind {
+        ErrorKind::UnexpectedChar { pos, expected: ExpectedChar::Char('=') }
+    }
+
+    #[test]
+    fn rebased_shifts_position() {
+        let input = "text/plain; a b";
+        let err = ParserErrorRef::new(&input[12..], unexpected(2)).rebased(input, 12);
+        assert_eq!(err.input(), input);
+        assert_eq!(err.kind(), unexpected(14));
+    }
+
+    #[test]
+    fn deepest_component_wins() {
+        let err = ParserErrorRef::new("", unexpected(0))
+            .in_component(Component::Comment)
+            .in_component(Component::ParamName);
+        assert_eq!(err.component(), Some(Component::Comment));
+    }
+
+    #[test]
+    fn context_is_not_compared() {
+        let input = "text/plain; a=[";
+        let err = ParserErrorRef::new(input, unexpected(14))
+            .in_component(Component::ParamValue)
+            .with_param_name(12, 13)
+            .in_param(0);
+        assert_eq!(err, ParserErrorRef::new(input, unexpected(14)));
+        assert_eq!(Error::from(err), Error::new(input, unexpected(14)));
+    }
+
+    #[test]
+    fn caret_display() {
+        let err = ParserErrorRef::new("text/plain; a b", unexpected(14))
+            .in_component(Component::ParamName);
+        assert_eq!(err.caret_display().to_string(), concat!(
+            "text/plain; a b\n",
+            "              ^ parsing hit an unexpected character (in parameter name)"
+        ));
+    }
+
+    #[test]
+    fn codes() {
+        assert_eq!(unexpected(3).code(), "unexpected-char");
+        assert_eq!(unexpected(3).cause_code(), None);
+        let kind = ErrorKind::QuotedParamValue { pos: 3, cause: CoreError::DoesNotEndWithDQuotes };
+        assert_eq!(kind.code(), "quoted-param-value");
+        assert_eq!(kind.cause_code(), Some("missing-end-dquotes"));
+        assert_eq!(ErrorKind::NameTooLong { pos: 127 }.code(), "name-too-long");
+    }
+
+    #[test]
+    fn caret_display_with_param_name() {
+        let err = ParserErrorRef::new("text/plain; a=[", unexpected(14))
+            .in_component(Component::ParamValue)
+            .with_param_name(12, 13)
+            .in_param(0);
+        assert_eq!(err.param_name(), Some("a"));
+        assert_eq!(err.param_index(), Some(0));
+        assert_eq!(err.to_owned().param_name(), Some("a"));
+        assert_eq!(err.caret_display().to_string(), concat!(
+            "text/plain; a=[\n",
+            "              ^ parsing hit an unexpected character (in parameter value of `a`)"
+        ));
+    }
+
+    #[test]
+    fn caret_display_counts_chars_not_bytes() {
+        let err = ParserErrorRef::new("text/plain; a=\"ä\" b", unexpected(19));
+        assert_eq!(err.caret_display().to_string(), concat!(
+            "text/plain; a=\"ä\" b\n",
+            "                  ^ parsing hit an unexpected character"
+        ));
+    }
+
+    #[test]
+    fn caret_display_escapes_line_breaks() {
+        let err = ParserErrorRef::new("text/plain;\r\n\ta b", unexpected(17));
+        assert_eq!(err.caret_display().to_string(), concat!(
+            "text/plain;\\r\\n\\ta b\n",
+            "                    ^ parsing hit an unexpected character"
+        ));
+    }
+
+    #[test]
+    fn caret_display_unexpected_eof() {
+        let err = ParserErrorRef::new("text/plain; a=\"b", ErrorKind::UnexpectedEof {
+            pos: 14, expected: ExpectedChar::Char('"')
+        });
+        assert_eq!(err.caret_display().to_string(), concat!(
+            "text/plain; a=\"b\n",
+            "              ^ parsing unexpectedly hit eof"
+        ));
+    }
+}
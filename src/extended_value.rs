@@ -0,0 +1,223 @@
+use std::borrow::Cow;
+use std::str;
+
+use error::{Error, ErrorKind, ExpectedChar};
+
+const PREFIX_SEP: char = '\'';
+
+/// A RFC 2231 (mime) / RFC 8187 (http) extended parameter value
+///
+/// Extended values are used with parameters which names end in `*`
+/// e.g. `title*=iso-8859-1'de'Gr%FC%DFe`. They consist of a charset,
+/// an optional language tag and the (percent-decoded) bytes of
+/// the value encoded in the given charset.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ExtendedValue<'a> {
+    charset: &'a str,
+    language: &'a str,
+    bytes: Cow<'a, [u8]>
+}
+
+impl<'a> ExtendedValue<'a> {
+
+    /// creates a new extended value
+    ///
+    /// An empty `language` means no language tag is specified, an empty `charset` means no charset is specified (RFC 2231 allows both
+    /// to be omitted).
+    ///
+    /// # Error
+    ///
+    /// An error is returned if the charset contains characters which are not
+    /// valid in a mime charset name, or if the language contains any chars
+    /// except us-ascii alphanumeric ones and `'-'`.
+    pub fn new<B>(charset: &'a str, language: &'a str, bytes: B) -> Result<Self, Error>
+        where B: Into<Cow<'a, [u8]>>
+    {
+        let value = ExtendedValue { charset, language, bytes: bytes.into() };
+        value.validate()?;
+        Ok(value)
+    }
+
+    /// creates a new utf-8 extended value without language tag
+    pub fn utf8(value: &'a str) -> Self {
+        ExtendedValue {
+            charset: "utf-8",
+            language: "",
+            bytes: Cow::Borrowed(value.as_bytes())
+        }
+    }
+
+    /// splits a not yet decoded `charset'language'value` into it's parts
+    ///
+    /// Returns the extended value without any bytes and the still
+    /// percent encoded value part.
+    pub(crate) fn split_encoded(encoded: &'a str) -> Option<(Self, &'a str)> {
+        let charset_end = encoded.find(PREFIX_SEP)?;
+        let language_start = charset_end + 1;
+        let language_end = language_start + encoded[language_start..].find(PREFIX_SEP)?;
+        let value = ExtendedValue {
+            charset: &encoded[..charset_end],
+            language: &encoded[language_start..language_end],
+            bytes: Cow::Borrowed(&[])
+        };
+        Some((value, &encoded[language_end+1..]))
+    }
+
+    /// creates a extended value without charset and language
+    pub(crate) fn unspecified(bytes: Vec<u8>) -> Self {
+        ExtendedValue { charset: "", language: "", bytes: Cow::Owned(bytes) }
+    }
+
+    pub(crate) fn with_bytes(self, bytes: Vec<u8>) -> Self {
+        ExtendedValue { bytes: Cow::Owned(bytes), ..self }
+    }
+
+    /// checks that the charset and language can be written into a media type
+    ///
+    /// Values created with `split_encoded` (i.e. from parsed input) are not
+    /// checked on creation, so this has to be done before writing them.
+    pub(crate) fn validate(&self) -> Result<(), Error> {
+        validate_charset(self.charset)?;
+        validate_language(self.language)
+    }
+
+    /// the charset, it's empty if it was not specified
+    pub fn charset(&self) -> &'a str {
+        self.charset
+    }
+
+    /// the language tag if one was specified
+    pub fn language(&self) -> Option<&'a str> {
+        if self.language.is_empty() {
+            None
+        } else {
+            Some(self.language)
+        }
+    }
+
+    /// the (not percent encoded) bytes of the value
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    pub fn into_bytes(self) -> Cow<'a, [u8]> {
+        self.bytes
+    }
+
+    /// converts the value to a string if it's charset is supported
+    ///
+    /// Supported charsets are `utf-8` and the charsets which are a subset of it
+    /// (`us-ascii`) as well as `iso-8859-1`. For other charsets or if the bytes
+    /// are not valid for the charset `None` is returned.
+    pub fn to_str(&self) -> Option<Cow<str>> {
+        let charset = self.charset;
+        if charset.eq_ignore_ascii_case("utf-8") || charset.eq_ignore_ascii_case("utf8") {
+            str::from_utf8(&self.bytes).ok().map(Cow::Borrowed)
+        } else if charset.eq_ignore_ascii_case("us-ascii") {
+            if self.bytes.is_ascii() {
+                str::from_utf8(&self.bytes).ok().map(Cow::Borrowed)
+            } else {
+                None
+            }
+        } else if charset.eq_ignore_ascii_case("iso-8859-1") {
+            Some(Cow::Owned(self.bytes.iter().map(|&bt| bt as char).collect()))
+        } else {
+            None
+        }
+    }
+}
+
+fn validate_charset(charset: &str) -> Result<(), Error> {
+    // mime-charset (RFC 2978) without '%' and '\'' which can't appear in the prefix
+    let invalid = charset.bytes().position(|bch| {
+        !(bch.is_ascii_alphanumeric() || b"!#$&+-^_`{}~".contains(&bch))
+    });
+    if let Some(pos) = invalid {
+        Err(Error::new(charset, ErrorKind::UnexpectedChar {
+            pos, expected: ExpectedChar::CharClass("mime-charset char")
+        }))
+    } else {
+        Ok(())
+    }
+}
+
+fn validate_language(language: &str) -> Result<(), Error> {
+    let invalid = language.bytes().position(|bch| !(bch.is_ascii_alphanumeric() || bch == b'-'));
+    if let Some(pos) = invalid {
+        Err(Error::new(language, ErrorKind::UnexpectedChar {
+            pos, expected: ExpectedChar::CharClass("language tag char")
+        }))
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::borrow::Cow;
+
+    use error::{Error, ErrorKind, ExpectedChar};
+    use super::ExtendedValue;
+
+    #[test]
+    fn split_encoded() {
+        let (value, tail) = ExtendedValue::split_encoded("iso-8859-1'de'Gr%FC%DFe").unwrap();
+        assert_eq!(value.charset(), "iso-8859-1");
+        assert_eq!(value.language(), Some("de"));
+        assert_eq!(tail, "Gr%FC%DFe");
+    }
+
+    #[test]
+    fn split_encoded_without_prefix() {
+        assert_eq!(ExtendedValue::split_encoded("Gr%FC%DFe"), None);
+        assert_eq!(ExtendedValue::split_encoded("utf-8'Gr%FC%DFe"), None);
+    }
+
+    #[test]
+    fn latin1_to_str() {
+        let value = assert_ok!(ExtendedValue::new("iso-8859-1", "de", &b"Gr\xfc\xdfe"[..]));
+        assert_eq!(value.to_str(), Some(Cow::Owned("Grüße".to_owned())));
+    }
+
+    #[test]
+    fn utf8_to_str() {
+        let value = ExtendedValue::utf8("Grüße");
+        assert_eq!(value.to_str(), Some(Cow::Borrowed("Grüße")));
+        let value = assert_ok!(ExtendedValue::new("UTF-8", "", &b"\xff"[..]));
+        assert_eq!(value.to_str(), None);
+    }
+
+    #[test]
+    fn unknown_charset_to_str() {
+        let value = assert_ok!(ExtendedValue::new("koi8-r", "", &b"abc"[..]));
+        assert_eq!(value.to_str(), None);
+    }
+
+    #[test]
+    fn invalid_charset() {
+        assert_eq!(ExtendedValue::new("utf'8", "", &b""[..]), Err(Error::new("utf'8",
+            ErrorKind::UnexpectedChar {
+                pos: 3,
+                expected: ExpectedChar::CharClass("mime-charset char")
+            }
+        )));
+        assert!(ExtendedValue::new("utf 8", "", &b""[..]).is_err());
+    }
+
+    #[test]
+    fn empty_charset() {
+        let value = assert_ok!(ExtendedValue::new("", "", &b"abc"[..]));
+        assert_eq!(value.charset(), "");
+        assert_eq!(value.language(), None);
+    }
+
+    #[test]
+    fn invalid_language() {
+        assert_eq!(ExtendedValue::new("utf-8", "de_DE", &b""[..]), Err(Error::new("de_DE",
+            ErrorKind::UnexpectedChar {
+                pos: 2,
+                expected: ExpectedChar::CharClass("language tag char")
+            }
+        )));
+    }
+}
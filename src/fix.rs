@@ -0,0 +1,268 @@
+//! fix-it suggestions for common mistakes in media types
+use std::fmt::{self, Display};
+
+use spec::Spec;
+use parse::find_unquoted;
+use media_type::MediaType;
+
+/// media types which are commonly used instead of the registered one
+const COMMON_MISNOMERS: &[(&str, &str)] = &[
+    ("image/jpg", "image/jpeg"),
+    ("image/pjpeg", "image/jpeg"),
+    ("image/tif", "image/tiff"),
+    ("image/svg", "image/svg+xml"),
+    ("audio/mp3", "audio/mpeg"),
+];
+
+/// A edit applied to the input by `suggest_fix`, positions are byte positions in the input
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Edit {
+    /// white space at the start/end or around a `=` was removed
+    RemovedWhitespace {
+        start: usize,
+        end: usize
+    },
+
+    /// a `;` not followed by a parameter was removed (e.g. a trailing `;`)
+    RemovedSemicolon {
+        pos: usize
+    },
+
+    /// a closing `"` was added for the quoted string starting at `pos`
+    ClosedQuotedString {
+        pos: usize
+    },
+
+    /// a commonly misused type/subtype (e.g. `image/jpg`) was replaced
+    ReplacedMediaType {
+        start: usize,
+        end: usize,
+        replacement: &'static str
+    }
+}
+
+impl Display for Edit {
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        use self::Edit::*;
+        match *self {
+            RemovedWhitespace { start, end } => write!(fter, "removed white space at {}..{}", start, end),
+            RemovedSemicolon { pos } => write!(fter, "removed stray ';' at {}", pos),
+            ClosedQuotedString { pos } => write!(fter, "closed quoted string starting at {}", pos),
+            ReplacedMediaType { start, end, replacement } => {
+                write!(fter, "replaced media type at {}..{} with {:?}", start, end, replacement)
+            }
+        }
+    }
+}
+
+/// A suggested fix for a media type, see `suggest_fix`
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Fix {
+    fixed: String,
+    edits: Vec<Edit>
+}
+
+impl Fix {
+
+    /// the fixed media type
+    pub fn fixed(&self) -> &str {
+        &self.fixed
+    }
+
+    /// the edits applied to the input, in the order of their position
+    pub fn edits(&self) -> &[Edit] {
+        &self.edits
+    }
+
+    pub fn into_fixed(self) -> String {
+        self.fixed
+    }
+}
+
+/// suggests a fix for a media type which can not be parsed under the spec `S`
+///
+/// Fixes trailing/duplicate `;`, white space around `=` or at the start/end,
+/// unterminated quoted strings and commonly misused media types like `image/jpg`.
+///
+/// Returns `None` if nothing was fixed or the fixed media type still doesn't
+/// parse under `S`. As misused media types parse fine, a fix can also be returned
+/// for inputs which parse.
+pub fn suggest_fix<S: Spec>(input: &str) -> Option<Fix> {
+    let mut edits = Vec::new();
+    let start = input.len() - input.trim_start().len();
+    let end = input.trim_end().len().max(start);
+    if start > 0 {
+        edits.push(Edit::RemovedWhitespace { start: 0, end: start });
+    }
+
+    let head_end = find_unquoted(input, start, b';').map(|idx| idx.min(end)).unwrap_or(end);
+    let head = &input[start..head_end];
+    let head_no_ws = head.trim_end();
+    let mut fixed = String::with_capacity(input.len());
+    let misnomer = COMMON_MISNOMERS.iter()
+        .find(|&&(wrong, _)| wrong.eq_ignore_ascii_case(head_no_ws));
+    if let Some(&(_, replacement)) = misnomer {
+        edits.push(Edit::ReplacedMediaType { start, end: start + head_no_ws.len(), replacement });
+        fixed.push_str(replacement);
+        fixed.push_str(&head[head_no_ws.len()..]);
+    } else {
+        fixed.push_str(head);
+    }
+
+    let mut sc_idx = head_end;
+    // the end of the head or the last parameter which was not removed
+    let mut kept_end = head_end;
+    while sc_idx < end {
+        let param_start = sc_idx + 1;
+        let param_end = find_unquoted(input, param_start, b';').map(|idx| idx.min(end)).unwrap_or(end);
+        let param = &input[param_start..param_end];
+        if param.trim().is_empty() {
+            edits.push(Edit::RemovedSemicolon { pos: sc_idx });
+        } else {
+            fixed.push(';');
+            push_param(&mut fixed, &mut edits, input, param_start, param_end);
+            kept_end = param_end;
+        }
+        sc_idx = param_end;
+    }
+    // white space in front of removed trailing `;`s would end up at the end
+    let ws_len = kept_end - input[..kept_end].trim_end().len();
+    if ws_len > 0 {
+        let fixed_len = fixed.len() - ws_len;
+        fixed.truncate(fixed_len);
+        edits.push(Edit::RemovedWhitespace { start: kept_end - ws_len, end: kept_end });
+    }
+
+    if end < input.len() {
+        edits.push(Edit::RemovedWhitespace { start: end, end: input.len() });
+    }
+    if edits.is_empty() || !MediaType::<S>::validate(&fixed) {
+        return None;
+    }
+    edits.sort_by_key(edit_pos);
+    Some(Fix { fixed, edits })
+}
+
+/// pushes the parameter at `start..end` removing white space around the `=` and closing quotes
+fn push_param(out: &mut String, edits: &mut Vec<Edit>, input: &str, start: usize, end: usize) {
+    let eq_idx = match find_unquoted(input, start, b'=') {
+        Some(idx) if idx < end => idx,
+        _ => {
+            out.push_str(&input[start..end]);
+            return;
+        }
+    };
+    let name = &input[start..eq_idx];
+    let name_no_ws = name.trim_end();
+    let value = &input[eq_idx + 1..end];
+    let value_no_ws = value.trim_start();
+    let value_start = end - value_no_ws.len();
+
+    out.push_str(name_no_ws);
+    if name_no_ws.len() < name.len() {
+        edits.push(Edit::RemovedWhitespace { start: start + name_no_ws.len(), end: eq_idx });
+    }
+    out.push('=');
+    if value_start > eq_idx + 1 {
+        edits.push(Edit::RemovedWhitespace { start: eq_idx + 1, end: value_start });
+    }
+    out.push_str(value_no_ws);
+    if value_no_ws.starts_with('"') && !is_closed_quoted_string(value_no_ws) {
+        edits.push(Edit::ClosedQuotedString { pos: value_start });
+        out.push('"');
+    }
+}
+
+/// true if the quoted string (starting with a `"`) is closed
+fn is_closed_quoted_string(quoted: &str) -> bool {
+    let mut escaped = false;
+    for ch in quoted[1..].chars() {
+        match ch {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => return true,
+            _ => {}
+        }
+    }
+    false
+}
+
+fn edit_pos(edit: &Edit) -> usize {
+    use self::Edit::*;
+    match *edit {
+        RemovedWhitespace { start, .. }
+        | ReplacedMediaType { start, .. } => start,
+        RemovedSemicolon { pos }
+        | ClosedQuotedString { pos } => pos
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use spec::{HttpSpec, MimeSpec, Obs, RegistrationSpec};
+    use super::{suggest_fix, Edit};
+
+    fn fixed(input: &str) -> String {
+        suggest_fix::<HttpSpec<Obs>>(input).unwrap().into_fixed()
+    }
+
+    #[test]
+    fn trailing_semicolon() {
+        let fix = suggest_fix::<HttpSpec<Obs>>("text/plain;charset=utf-8;").unwrap();
+        assert_eq!(fix.fixed(), "text/plain;charset=utf-8");
+        assert_eq!(fix.edits(), &[Edit::RemovedSemicolon { pos: 24 }]);
+        let fix = suggest_fix::<HttpSpec<Obs>>("text/plain;; a=b ; ").unwrap();
+        assert_eq!(fix.fixed(), "text/plain; a=b");
+        assert_eq!(fix.edits(), &[
+            Edit::RemovedSemicolon { pos: 10 },
+            Edit::RemovedWhitespace { start: 16, end: 17 },
+            Edit::RemovedSemicolon { pos: 17 },
+            Edit::RemovedWhitespace { start: 18, end: 19 }
+        ]);
+        assert_eq!(fixed("text/plain ;"), "text/plain");
+        assert_eq!(fixed("text/plain; a=b ; c=d;"), "text/plain; a=b ; c=d");
+    }
+
+    #[test]
+    fn spaces_around_eq() {
+        let fix = suggest_fix::<HttpSpec<Obs>>("application/json; charset = utf-8").unwrap();
+        assert_eq!(fix.fixed(), "application/json; charset=utf-8");
+        assert_eq!(fix.edits(), &[
+            Edit::RemovedWhitespace { start: 25, end: 26 },
+            Edit::RemovedWhitespace { start: 27, end: 28 }
+        ]);
+    }
+
+    #[test]
+    fn unterminated_quote() {
+        let fix = suggest_fix::<HttpSpec<Obs>>("text/html; charset=\"utf-8").unwrap();
+        assert_eq!(fix.fixed(), "text/html; charset=\"utf-8\"");
+        assert_eq!(fix.edits(), &[Edit::ClosedQuotedString { pos: 19 }]);
+        assert_eq!(fixed("text/html; a=\"b;c"), "text/html; a=\"b;c\"");
+    }
+
+    #[test]
+    fn misused_media_type() {
+        let fix = suggest_fix::<RegistrationSpec>("Image/JPG; a=b").unwrap();
+        assert_eq!(fix.fixed(), "image/jpeg; a=b");
+        assert_eq!(fix.edits(), &[Edit::ReplacedMediaType { start: 0, end: 9, replacement: "image/jpeg" }]);
+    }
+
+    #[test]
+    fn surrounding_whitespace() {
+        let fix = suggest_fix::<HttpSpec<Obs>>(" text/plain;\t").unwrap();
+        assert_eq!(fix.fixed(), "text/plain");
+        assert_eq!(fix.edits(), &[
+            Edit::RemovedWhitespace { start: 0, end: 1 },
+            Edit::RemovedSemicolon { pos: 11 },
+            Edit::RemovedWhitespace { start: 12, end: 13 }
+        ]);
+    }
+
+    #[test]
+    fn no_fix() {
+        assert_eq!(suggest_fix::<HttpSpec<Obs>>("text/plain; charset=utf-8"), None);
+        assert_eq!(suggest_fix::<HttpSpec<Obs>>("text/pl[ain;"), None);
+        assert_eq!(suggest_fix::<MimeSpec>("text/plain; a=\"b\\"), None);
+    }
+}
@@ -1,15 +1,24 @@
+use std::fmt;
+
 use quoted_string::quote_if_needed;
-use percent_encoding::percent_encode;
+use quoted_string::spec::{
+    GeneralQSSpec, QuotingClassifier, QuotingClass,
+    WithoutQuotingValidator, PartialCodePoint
+};
+use percent_encoding::{percent_encode, EncodeSet};
 
-use parse::ParamIndices;
+use parse::{ParamIndices, find_plus_idx};
 use spec::Spec;
 use error::Error;
+use extended_value::ExtendedValue;
 
 const PARAM_SEP: &str = "; ";
 const PARAM_KV_SEP: char = '=';
 const TYPE_SEP: char = '/';
 const PARAM_ENC_NAME_SUFFIC: char = '*';
-const PARAM_ENC_VALUE_PREFIX: &str = "utf-8''";
+const PARAM_SECTION_SEP: char = '*';
+const FOLD: &str = "\r\n ";
+const PARAM_ENC_PREFIX_SEP: char = '\'';
 
 //TODO replace key=value: (AsRef<str>, AsRef<str>) with T: KeyValue
 // where trait KeyValue { fn key -> &str, fn value -> ??, fn lang_tag -> Option<&str> }
@@ -17,13 +26,16 @@ const PARAM_ENC_VALUE_PREFIX: &str = "utf-8''";
 // not that fn value -> ?? has to work in a way that it can handle ->Value<- with to_content
 // (we can't use repr, as we do not know if Value Spec if compatible with out Spec)
 
+/// creates a buffer containing `type/subtype`
+///
+/// Returns the buffer, the `slash_idx`, the `plus_idx` and the `end_of_type` index.
 pub(crate) fn create_buffer_from<S>(
     type_: &str, subtype: &str
-) -> Result<(String, usize, usize), Error>
+) -> Result<(String, usize, usize, usize), Error>
     where S: Spec
 {
-    S::validate_token(type_)?;
-    S::validate_token(subtype)?;
+    S::validate_type(type_)?;
+    S::validate_subtype(subtype)?;
 
     let mut buffer = String::new();
 
@@ -33,7 +45,8 @@ pub(crate) fn create_buffer_from<S>(
     buffer.push(TYPE_SEP);
     buffer.push_str(subtype);
     let end_of_type = buffer.len();
-    Ok((buffer, slash_idx, end_of_type))
+    let plus_idx = find_plus_idx(&buffer, slash_idx, end_of_type);
+    Ok((buffer, slash_idx, plus_idx, end_of_type))
 }
 
 /// Push all parameters formatted to the output buffer
@@ -60,8 +73,7 @@ pub fn push_params_to_buffer<S, I, IN, IV>(buffer: &mut String, params: I)
     for (name, value) in params.into_iter() {
         let name = <IN as AsRef<str>>::as_ref(&name);
         let value = <IV as AsRef<str>>::as_ref(&value);
-        let indices = push_param_to_buffer::<S>(buffer, name, value)?;
-        param_indices.push(indices);
+        push_param_to_buffer::<S>(buffer, name, value, &mut param_indices)?;
     }
 
     Ok(param_indices)
@@ -74,35 +86,92 @@ pub fn push_params_to_buffer<S, I, IN, IV>(buffer: &mut String, params: I)
 /// if the value needs to be encoded it's encoded (and "*" is added to the
 /// parameter name.
 ///
+/// If the parameter is longer then `S::param_split_threshold()` it is split
+/// into multiple RFC 2231 continuations (`name*0=..; name*1=..`). The indices
+/// of all pushed sections are added to `indices`.
+///
 /// # Error
 ///
 /// an error is returned if the parameter name is not valid for the given
-/// Spec `S`.
+/// Spec `S`, or if the value needs to be encoded but `S` does not allow
+/// encoded parameters (e.g. `StrictSpec`, see `push_extended_param_to_buffer`).
 ///
-pub fn push_param_to_buffer<S>(buffer: &mut String, name: &str, value: &str)
-                                           -> Result<ParamIndices, Error>
+pub fn push_param_to_buffer<S>(
+    buffer: &mut String, name: &str, value: &str, indices: &mut Vec<ParamIndices>
+) -> Result<(), Error>
     where S: Spec
 {
     S::validate_token(name)?;
-    //TODO percent encode+split if value > threshold && it's MIME spec
-    //TODO important aboves TODO might change this TODO's interface
-    Ok(match quote_if_needed::<S, _>(value, &mut S::UnquotedValue::default()) {
+    match quote_if_needed::<S, _>(value, &mut S::UnquotedValue::default()) {
         Ok(quoted_if_needed) => {
-            //TODO if > threashold fall back to encodinf
-            let value = quoted_if_needed.as_ref();
-            _buffer_push_param(buffer, name, value)
+            let quoted = quoted_if_needed.as_ref();
+            match S::param_split_threshold() {
+                // an empty value has nothing to split, and if the name leaves no room
+                // for the value splitting would only produce sections exceeding `max`
+                Some(max) if !value.is_empty()
+                    && name.len() + 1 + quoted.len() > max
+                    && section_header_len(name, 0, false) < max =>
+                {
+                    _buffer_split_and_push_param::<S>(buffer, name, value, max, indices)
+                },
+                _ => {
+                    indices.push(_buffer_push_param(buffer, name, None, false, quoted))
+                }
+            }
         },
         Err(_err) => {
-            _buffer_encode_and_push_param::<S>(buffer, name, value)
+            let value = ExtendedValue::utf8(value);
+            let (encoded, prefix_len) = encode_extended_value::<S>(name, &value)?;
+            push_encoded_param_to_buffer::<S>(buffer, name, &encoded, prefix_len, indices)
         }
-    })
+    }
+    Ok(())
+}
+
+/// Push one parameter with an extended value to the output buffer
+///
+/// the parameter is preceded by "; " followed by <name> then "*=",
+/// then the charset, language and percent encoded bytes of the value
+/// (e.g. `; title*=iso-8859-1'de'Gr%FC%DFe`). Like with `push_param_to_buffer`
+/// the parameter is split into continuations if it's to long.
+///
+/// # Error
+///
+/// an error is returned if the parameter name is not valid for the given
+/// Spec `S` or if the charset or language of the value contains chars which
+/// are not allowed in them (which is possible for values decoded from a
+/// parsed media type). As extended values can not be quoted an error is also
+/// returned if `<name>*` or the encoded value are not valid tokens under `S`
+/// (e.g. `StrictSpec` does neither allow `*` nor `'` in tokens).
+///
+pub fn push_extended_param_to_buffer<S>(
+    buffer: &mut String, name: &str, value: &ExtendedValue, indices: &mut Vec<ParamIndices>
+) -> Result<(), Error>
+    where S: Spec
+{
+    S::validate_token(name)?;
+    value.validate()?;
+    let (encoded, prefix_len) = encode_extended_value::<S>(name, value)?;
+    push_encoded_param_to_buffer::<S>(buffer, name, &encoded, prefix_len, indices);
+    Ok(())
 }
 
-fn _buffer_push_param(buffer: &mut String, name: &str, value: &str) -> ParamIndices {
+/// pushes `; <name>[*<section>][*]=<value>`
+fn _buffer_push_param(
+    buffer: &mut String, name: &str, section: Option<usize>, extended: bool, value: &str
+) -> ParamIndices
+{
     buffer.push_str(PARAM_SEP);
     let start = buffer.len();
 
     buffer.push_str(name);
+    if let Some(section) = section {
+        buffer.push(PARAM_SECTION_SEP);
+        buffer.push_str(&section.to_string());
+    }
+    if extended {
+        buffer.push(PARAM_ENC_NAME_SUFFIC);
+    }
     let eq_idx = buffer.len();
 
     buffer.push(PARAM_KV_SEP);
@@ -112,27 +181,248 @@ fn _buffer_push_param(buffer: &mut String, name: &str, value: &str) -> ParamIndi
     ParamIndices { start, eq_idx, end }
 }
 
-fn _buffer_encode_and_push_param<S: Spec>(
-    buffer: &mut String, name: &str, value: &str
-) -> ParamIndices
+/// the length of `<name>*<section>[*]=`
+fn section_header_len(name: &str, section: usize, extended: bool) -> usize {
+    let extended_len = if extended { 1 } else { 0 };
+    name.len() + 1 + section.to_string().len() + extended_len + 1
+}
+
+/// splits a value which can be represented without encoding into multiple sections
+///
+/// each section is quoted if needed, at last one char is placed in each section
+/// even if this exceeds `max`
+fn _buffer_split_and_push_param<S: Spec>(
+    buffer: &mut String, name: &str, value: &str, max: usize, indices: &mut Vec<ParamIndices>
+) {
+    let mut section = 0;
+    let mut rest = value;
+    while !rest.is_empty() {
+        let budget = max.saturating_sub(section_header_len(name, section, false));
+        let chunk_end = _split_point::<S>(rest, budget);
+        let quoted = quote_if_needed::<S, _>(&rest[..chunk_end], &mut S::UnquotedValue::default())
+            .expect("[BUG] part of a quotable value is not quotable");
+        indices.push(_buffer_push_param(buffer, name, Some(section), false, &quoted));
+        rest = &rest[chunk_end..];
+        section += 1;
+    }
+}
+
+/// returns the end of the longest prefix of `value` which fits into `budget` once quoted
+///
+/// The prefix contains at last one char. The length the prefix would have once
+/// quoted is tracked char by char, so this is linear in the length of the prefix.
+fn _split_point<S: Spec>(value: &str, budget: usize) -> usize {
+    let mut validator = S::UnquotedValue::default();
+    let mut can_be_unquoted = true;
+    // the length if written as quoted string without the surrounding quotes
+    let mut escaped_len = 0;
+    let mut end = 0;
+    for (idx, ch) in value.char_indices() {
+        let pcp = PartialCodePoint::from_code_point(ch as u32);
+        can_be_unquoted = can_be_unquoted && validator.next(pcp);
+        escaped_len += ch.len_utf8();
+        if let QuotingClass::NeedsQuoting = <S as GeneralQSSpec>::Quoting::classify_for_quoting(pcp) {
+            escaped_len += 1;
+        }
+        let candidate_end = idx + ch.len_utf8();
+        let len =
+            if can_be_unquoted && validator.end() { candidate_end }
+            else { escaped_len + 2 };
+        if end > 0 && len > budget {
+            break;
+        }
+        end = candidate_end;
+    }
+    end
+}
+
+/// wraps a `EncodeSet` to additionally encode the chars which are not attribute-char's (RFC 2231)
+#[derive(Copy, Clone, Debug, Default)]
+struct AttributeCharEncodeSet<E>(E);
+
+impl<E: EncodeSet> EncodeSet for AttributeCharEncodeSet<E> {
+    fn contains(&self, byte: u8) -> bool {
+        self.0.contains(byte) || byte == b'*' || byte == b'\'' || byte == b'%'
+    }
+}
+
+/// encodes the value as `charset'language'percent-encoded-bytes`
+///
+/// Returns the encoded value and the length of it's `charset'language'` prefix.
+/// The charset and language of the value are expected to be valid.
+///
+/// # Error
+///
+/// An error is returned if `<name>*` is not a valid token or the encoded value
+/// is not a valid unquoted value under `S`.
+pub(crate) fn encode_extended_value<S: Spec>(name: &str, value: &ExtendedValue)
+    -> Result<(String, usize), Error>
 {
-    let encoded_value_parts =
-        percent_encode(value.as_bytes(), S::PercentEncodeSet::default());
+    let encode_set = AttributeCharEncodeSet(S::PercentEncodeSet::default());
 
-    buffer.push_str(PARAM_SEP);
+    let mut encoded = String::new();
+    encoded.push_str(value.charset());
+    encoded.push(PARAM_ENC_PREFIX_SEP);
+    encoded.push_str(value.language().unwrap_or(""));
+    encoded.push(PARAM_ENC_PREFIX_SEP);
+    // the prefix has to be in the first section
+    let prefix_len = encoded.len();
+    for value_part in percent_encode(value.bytes(), encode_set) {
+        encoded.push_str(value_part);
+    }
 
-    let start = buffer.len();
-    buffer.push_str(name);
-    buffer.push(PARAM_ENC_NAME_SUFFIC);
+    // the section names and values are made of the same chars, so it's
+    // enough to validate the unsplit parameter
+    let mut encoded_name = String::from(name);
+    encoded_name.push(PARAM_ENC_NAME_SUFFIC);
+    S::validate_token(&encoded_name)?;
+    S::validate_unquoted_value(&encoded)?;
+    Ok((encoded, prefix_len))
+}
 
-    let eq_idx = buffer.len();
-    buffer.push(PARAM_KV_SEP);
+/// pushes a value returned by `encode_extended_value`, splitting it if needed
+pub(crate) fn push_encoded_param_to_buffer<S: Spec>(
+    buffer: &mut String, name: &str, encoded: &str, prefix_len: usize,
+    indices: &mut Vec<ParamIndices>
+) {
+    let max = match S::param_split_threshold() {
+        Some(max) if name.len() + 2 + encoded.len() > max
+            && section_header_len(name, 0, true) + prefix_len < max => max,
+        _ => {
+            indices.push(_buffer_push_param(buffer, name, None, true, encoded));
+            return;
+        }
+    };
 
-    buffer.push_str(PARAM_ENC_VALUE_PREFIX);
-    for value_part in encoded_value_parts {
-        buffer.push_str(value_part);
+    // '%' is always encoded so behind the prefix it only appears in "%XX" triplets
+    let piece_len = |idx: usize| if encoded.as_bytes()[idx] == b'%' { 3 } else { 1 };
+    let mut section = 0;
+    let mut start = 0;
+    let mut end = prefix_len;
+    loop {
+        let budget = max.saturating_sub(section_header_len(name, section, true));
+        if end < encoded.len() {
+            end += piece_len(end);
+        }
+        while end < encoded.len() && end + piece_len(end) - start <= budget {
+            end += piece_len(end);
+        }
+        indices.push(_buffer_push_param(buffer, name, Some(section), true, &encoded[start..end]));
+        if end >= encoded.len() {
+            break;
+        }
+        section += 1;
+        start = end;
     }
-    let end = buffer.len();
+}
 
-    ParamIndices { start, eq_idx, end }
-}
\ No newline at end of file
+/// Writes the media type in `buffer` folded to `out`
+///
+/// A line is only folded at parameter boundaries by replacing the white space behind
+/// the `;` with `"\r\n "`. A line is folded if the next parameter would exceed
+/// `max_width`, through single parameters longer then `max_width` can still
+/// exceed it. Separators which already contain a fold are kept as they are.
+///
+/// `initial_offset` is the length of anything already written in the first line,
+/// e.g. `"Content-Type: ".len()`.
+pub(crate) fn write_folded<W>(
+    out: &mut W,
+    buffer: &str,
+    end_of_type: usize,
+    params: &[ParamIndices],
+    initial_offset: usize,
+    max_width: usize
+) -> fmt::Result
+    where W: fmt::Write
+{
+    let head = &buffer[..end_of_type];
+    out.write_str(head)?;
+    let mut column = column_after(initial_offset, head);
+    let mut prev_end = end_of_type;
+    for indices in params {
+        let sep = &buffer[prev_end..indices.start];
+        let param = &buffer[indices.start..indices.end];
+        let too_long = column + sep.chars().count() + param.chars().count() > max_width;
+        if too_long && !sep.contains('\n') {
+            let sep = sep.trim_end_matches(&[' ', '\t'][..]);
+            out.write_str(sep)?;
+            out.write_str(FOLD)?;
+            column = FOLD.len() - 2;
+        } else {
+            out.write_str(sep)?;
+            column = column_after(column, sep);
+        }
+        out.write_str(param)?;
+        column = column_after(column, param);
+        prev_end = indices.end;
+    }
+    Ok(())
+}
+
+/// returns the column after writing `text` starting at `column`
+fn column_after(column: usize, text: &str) -> usize {
+    match text.rfind('\n') {
+        Some(idx) => text[idx+1..].chars().count(),
+        None => column + text.chars().count()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use parse::ParamIndices;
+    use super::write_folded;
+
+    fn fold(buffer: &str, end_of_type: usize, params: &[ParamIndices], offset: usize, max: usize)
+        -> String
+    {
+        let mut out = String::new();
+        write_folded(&mut out, buffer, end_of_type, params, offset, max).unwrap();
+        out
+    }
+
+    fn indices(start: usize, eq_idx: usize, end: usize) -> ParamIndices {
+        ParamIndices { start, eq_idx, end }
+    }
+
+    #[test]
+    fn no_params() {
+        assert_eq!(fold("text/plain", 10, &[], 14, 10), "text/plain");
+    }
+
+    #[test]
+    fn fits_in_line() {
+        let buffer = "text/plain; charset=utf-8";
+        assert_eq!(fold(buffer, 10, &[indices(12, 19, 25)], 0, 25), buffer);
+    }
+
+    #[test]
+    fn folds_if_needed() {
+        let buffer = "text/plain; charset=utf-8";
+        assert_eq!(
+            fold(buffer, 10, &[indices(12, 19, 25)], 1, 25),
+            "text/plain;\r\n charset=utf-8"
+        );
+    }
+
+    #[test]
+    fn folds_without_whitespace_and_with_comments() {
+        let buffer = "text/plain;charset=utf-8 (c) ;a=b";
+        assert_eq!(
+            fold(buffer, 10, &[indices(11, 18, 24), indices(30, 31, 33)], 0, 10),
+            "text/plain;\r\n charset=utf-8 (c) ;\r\n a=b"
+        );
+    }
+
+    #[test]
+    fn keeps_existing_folds() {
+        let buffer = "text/plain;\r\n charset=utf-8; a=b";
+        assert_eq!(
+            fold(buffer, 10, &[indices(14, 21, 27), indices(29, 30, 32)], 0, 20),
+            "text/plain;\r\n charset=utf-8; a=b"
+        );
+        assert_eq!(
+            fold(buffer, 10, &[indices(14, 21, 27), indices(29, 30, 32)], 0, 16),
+            "text/plain;\r\n charset=utf-8;\r\n a=b"
+        );
+    }
+}
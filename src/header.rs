@@ -0,0 +1,64 @@
+//! splitting of header lines (e.g. `Content-Type: text/plain\r\n`)
+use error::{ParserErrorRef, ErrorKind, ExpectedChar};
+
+pub(crate) const CONTENT_TYPE: &str = "Content-Type";
+
+/// splits a header line into the start and end of it's value
+///
+/// The name is compared ascii case-insensitive with `name`, the value starts
+/// after the `:` and leading white space and ends before trailing white space
+/// and a trailing `"\r\n"` (or `"\n"`).
+pub(crate) fn split_header_line<'a>(line: &'a str, name: &str) -> Result<(usize, usize), ParserErrorRef<'a>> {
+    let colon_idx = match line.find(':') {
+        Some(idx) => idx,
+        None => {
+            return Err(ErrorKind::UnexpectedEof {
+                pos: line.len(), expected: ExpectedChar::Char(':')
+            }.with_input(line))
+        }
+    };
+    if !line[..colon_idx].eq_ignore_ascii_case(name) {
+        return Err(ErrorKind::InvalidHeaderName { pos: 0 }.with_input(line));
+    }
+    let value_start = line.len() - line[colon_idx + 1..].trim_start_matches(is_ws).len();
+    let line_end_len = if line.ends_with("\r\n") {
+        2
+    } else if line.ends_with('\n') {
+        1
+    } else {
+        0
+    };
+    let value_end = line[..line.len() - line_end_len].trim_end_matches(is_ws).len().max(value_start);
+    Ok((value_start, value_end))
+}
+
+fn is_ws(ch: char) -> bool {
+    ch == ' ' || ch == '\t'
+}
+
+#[cfg(test)]
+mod test {
+    use error::{ErrorKind, ExpectedChar};
+    use super::{split_header_line, CONTENT_TYPE};
+
+    fn value(line: &str) -> &str {
+        let (start, end) = split_header_line(line, CONTENT_TYPE).unwrap();
+        &line[start..end]
+    }
+
+    #[test]
+    fn splits_value() {
+        assert_eq!(value("Content-Type: text/plain\r\n"), "text/plain");
+        assert_eq!(value("content-type:text/plain \t\n"), "text/plain");
+        assert_eq!(value("CONTENT-TYPE:\ttext/plain;\r\n charset=utf-8"), "text/plain;\r\n charset=utf-8");
+        assert_eq!(value("Content-Type:  \r\n"), "");
+    }
+
+    #[test]
+    fn checks_name() {
+        let err = split_header_line("Content-Length: 12", CONTENT_TYPE).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidHeaderName { pos: 0 });
+        let err = split_header_line("Content-Type text/plain", CONTENT_TYPE).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof { pos: 23, expected: ExpectedChar::Char(':') });
+    }
+}
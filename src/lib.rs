@@ -19,12 +19,19 @@ extern crate lut;
 pub use quoted_string::AsciiCaseInsensitiveEq;
 pub use self::name::*;
 pub use self::value::*;
-pub use self::media_type::{MediaType, AnyMediaType, Params};
+pub use self::media_type::{MediaType, AnyMediaType, Params, RevalidationError};
+pub use self::media_range::MediaRange;
+pub use self::tree::RegistrationTree;
+pub use self::accept::{Accept, AcceptEntry, Quality, negotiate};
+pub use self::fix::{suggest_fix, Fix, Edit};
+pub use self::raw::{RawMediaType, RawValue, RawParams};
+pub use self::decode::{DecodedParams, DecodedParam, DecodedValue};
+pub use self::extended_value::ExtendedValue;
 
 #[cfg(feature="expose-param-utils")]
 pub use parse::ParamIndices;
 #[cfg(feature="expose-param-utils")]
-pub use gen::push_params_to_buffer;
+pub use gen::{push_params_to_buffer, push_extended_param_to_buffer};
 
 #[macro_use]
 mod macros;
@@ -34,6 +41,15 @@ mod value;
 pub mod spec;
 mod parse;
 mod media_type;
+mod media_range;
+mod tree;
+mod whatwg;
+mod accept;
+mod fix;
+mod raw;
+mod header;
+mod decode;
+mod extended_value;
 mod gen;
 
 mod seal {
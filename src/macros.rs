@@ -31,10 +31,23 @@ macro_rules! assert_ok {
 /// let fb_end = parse_foobar(&input[fb_start..])? + fb_start;
 /// let end = parse_suffix(&input[fb_end..])? + fb_end;
 /// ```
+///
+/// Errors are rebased to be relative to `input` instead of the sub-slice, if
+/// a `Component` is given (`at_pos!(offset do parse_foobar | input; component)`)
+/// it is set on the error if it does not have one yet.
 macro_rules! at_pos {
     ($offset:ident do $pfn:path | $input:expr ) => {
-        $pfn(&$input[$offset..])? + $offset
-    }
+        match $pfn(&$input[$offset..]) {
+            Ok(end) => end + $offset,
+            Err(err) => return Err(err.rebased($input, $offset))
+        }
+    };
+    ($offset:ident do $pfn:path | $input:expr ; $component:expr) => {
+        match $pfn(&$input[$offset..]) {
+            Ok(end) => end + $offset,
+            Err(err) => return Err(err.rebased($input, $offset).in_component($component))
+        }
+    };
 }
 
 //#[doc(hidden)]
@@ -0,0 +1,194 @@
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::fmt::{self, Display};
+
+use error::ParserErrorRef;
+use media_type::{AnyMediaType, param_values_eq};
+use parse::{Spec, ParseResult, parse_range};
+
+const WILDCARD: &str = "*";
+
+/// A media range like `text/*` or `*/*` as used e.g. in the http `Accept` header
+///
+/// Besides allowing the wildcard `*` for the type and/or subtype it works like
+/// a `MediaType<S>`, i.e. it is parsed with the same spec and derefs to a
+/// `AnyMediaType`.
+#[derive(Clone, Debug)]
+pub struct MediaRange<S: Spec> {
+    inner: AnyMediaType,
+    _spec: PhantomData<S>
+}
+
+impl<S> MediaRange<S>
+    where S: Spec
+{
+    /// parses a media range
+    ///
+    /// The wildcard `*` is accepted as type and subtype even if `*` is not a
+    /// valid token character in `S`. A wildcard type with a non wildcard
+    /// subtype (e.g. `*/json`) is rejected.
+    pub fn parse(input: &str) -> Result<Self, ParserErrorRef> {
+        let parse_result: ParseResult = parse_range::<S>(input)?;
+        let media_type: AnyMediaType = parse_result.into();
+        Ok(MediaRange { inner: media_type, _spec: PhantomData })
+    }
+
+    pub fn validate(input: &str) -> bool {
+        parse_range::<S>(input).is_ok()
+    }
+
+    /// true if type and subtype are wildcards (`*/*`)
+    pub fn is_any_type(&self) -> bool {
+        self.type_().as_str() == WILDCARD
+    }
+
+    /// true if the subtype is a wildcard (`text/*` or `*/*`)
+    pub fn is_any_subtype(&self) -> bool {
+        self.subtype().as_str() == WILDCARD
+    }
+
+    /// returns how specific the range is, higher values are more specific
+    ///
+    /// Ranges are ordered by the number of wildcards and then by the number
+    /// of parameters, i.e. `*/*` < `text/*` < `text/plain` < `text/plain; format=flowed`.
+    pub(crate) fn specificity(&self) -> (u8, usize) {
+        let level = if self.is_any_type() {
+            0
+        } else if self.is_any_subtype() {
+            1
+        } else {
+            2
+        };
+        (level, self.params().len())
+    }
+
+    /// returns true if the media type is in this range
+    ///
+    /// This is the case if type and subtype are equal or wildcards
+    /// and all parameters of the media range are also parameters
+    /// of the media type with the same value (RFC 9110 section 12.5.1).
+    /// Parameters are compared the same way `AnyMediaType`'s `PartialEq`
+    /// implementation compares them, i.e. the values of the parameters in
+    /// `CASE_INSENSITIVE_PARAMS` are compared case-insensitive.
+    pub fn matches(&self, media_type: &AnyMediaType) -> bool {
+        if !self.is_any_type() && self.type_() != media_type.type_() {
+            return false;
+        }
+        if !self.is_any_subtype() && self.subtype() != media_type.subtype() {
+            return false;
+        }
+        self.params().all(|(name, value)| {
+            media_type.get_param(name)
+                .map(|other_value| param_values_eq(name, value, other_value))
+                .unwrap_or(false)
+        })
+    }
+}
+
+impl<'a, S> From<ParseResult<'a>> for MediaRange<S>
+    where S: Spec
+{
+    /// the parse result is expected to be produced by `parse_range::<S>`
+    fn from(pres: ParseResult<'a>) -> Self {
+        MediaRange { inner: pres.into(), _spec: PhantomData }
+    }
+}
+
+impl<S1, S2> PartialEq<MediaRange<S2>> for MediaRange<S1>
+    where S1: Spec, S2: Spec
+{
+    fn eq(&self, other: &MediaRange<S2>) -> bool {
+        self.deref() == other.deref()
+    }
+}
+
+impl<S> Deref for MediaRange<S>
+    where S: Spec
+{
+    type Target = AnyMediaType;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl<S> Display for MediaRange<S>
+    where S: Spec
+{
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        fter.write_str(self.as_str_repr())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use error::{ErrorKind, ExpectedChar};
+    use media_type::MediaType;
+    use spec::{HttpSpec, StrictSpec, Modern};
+    use super::MediaRange;
+
+    type Range = MediaRange<HttpSpec<Modern>>;
+    type Type = MediaType<HttpSpec<Modern>>;
+
+    fn matches(range: &str, media_type: &str) -> bool {
+        let range = assert_ok!(Range::parse(range));
+        let media_type = assert_ok!(Type::parse(media_type));
+        range.matches(&media_type)
+    }
+
+    #[test]
+    fn parse_wildcards() {
+        let range = assert_ok!(Range::parse("*/*"));
+        assert!(range.is_any_type());
+        assert!(range.is_any_subtype());
+        let range = assert_ok!(Range::parse("Text/*"));
+        assert!(!range.is_any_type());
+        assert!(range.is_any_subtype());
+        assert_eq!(range.type_(), "text");
+        let range = assert_ok!(Range::parse("text/plain; charset=utf-8"));
+        assert!(!range.is_any_type());
+        assert!(!range.is_any_subtype());
+    }
+
+    #[test]
+    fn parse_wildcard_in_strict_spec() {
+        assert_ok!(MediaRange::<StrictSpec>::parse("*/*"));
+        assert_ok!(MediaRange::<StrictSpec>::parse("text/*"));
+        assert!(MediaRange::<StrictSpec>::parse("text/*a").is_err());
+    }
+
+    #[test]
+    fn reject_wildcard_type_with_subtype() {
+        let err = Range::parse("*/json").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedChar {
+            pos: 2, expected: ExpectedChar::Char('*')
+        });
+    }
+
+    #[test]
+    fn matches_wildcards() {
+        assert!(matches("*/*", "text/plain"));
+        assert!(matches("text/*", "text/plain; charset=utf-8"));
+        assert!(!matches("text/*", "image/png"));
+        assert!(matches("text/plain", "TEXT/Plain"));
+        assert!(!matches("text/plain", "text/html"));
+    }
+
+    #[test]
+    fn matches_params() {
+        assert!(matches("text/plain; format=flowed", "text/plain; charset=utf-8; format=flowed"));
+        assert!(!matches("text/plain; format=flowed", "text/plain; format=fixed"));
+        assert!(!matches("text/plain; format=flowed", "text/plain"));
+        assert!(matches("text/plain; format=\"flowed\"", "text/plain; format=flowed"));
+        assert!(matches("text/*; charset=utf-8", "text/html; charset=utf-8"));
+        assert!(!matches("text/*; charset=utf-8", "text/html; charset=latin1"));
+        assert!(!matches("text/plain; a=B", "text/plain; a=b"));
+    }
+
+    #[test]
+    fn matches_case_insensitive_params() {
+        assert!(matches("text/*; charset=UTF-8", "text/html; charset=utf-8"));
+        assert!(matches("text/*; charset=utf8", "text/html; charset=\"UTF-8\""));
+        assert!(!matches("text/plain; format=Flowed", "text/plain; format=flowed"));
+    }
+}
@@ -1,20 +1,36 @@
-use std::collections::HashMap;
+use std::borrow::Cow;
+use std::hash::{Hash, Hasher};
+use std::cmp::Ordering;
 use std::iter::{Iterator, ExactSizeIterator};
 use std::slice;
 use std::marker::PhantomData;
 use std::ops::{Deref, DerefMut};
 use std::fmt::{self, Debug, Display};
+use std::error::Error as StdError;
 
+use quoted_string::AsciiCaseInsensitiveEq;
 use error::{Error, ParserErrorRef};
-use name::{Name, CHARSET, MULTIPART};
+use decode::{DecodedParams, DecodedValue, split_section_name};
+use extended_value::ExtendedValue;
+use name::{Name, CHARSET, MULTIPART, CASE_INSENSITIVE_PARAMS};
+use tree::RegistrationTree;
 use value::{Value, UTF_8, UTF8};
 use gen::{
     create_buffer_from,
     push_params_to_buffer,
-    push_param_to_buffer
+    push_param_to_buffer,
+    push_extended_param_to_buffer,
+    encode_extended_value,
+    push_encoded_param_to_buffer,
+    write_folded
 };
 
-use parse::{Spec, ParseResult, ParamIndices, parse, validate};
+use parse::{
+    Spec, FoldingSpec, AnySpec, ParseResult, ParamIndices,
+    parse, parse_recovering, validate, find_plus_idx
+};
+use whatwg;
+use header::{split_header_line, CONTENT_TYPE};
 
 
 #[derive(Clone, Debug)]
@@ -32,19 +48,118 @@ impl<S> MediaType<S>
         Ok(MediaType { inner: media_type, _spec: PhantomData })
     }
 
+    /// parses the media type skipping invalid parameters instead of failing
+    ///
+    /// Returns the media type with all parameters which could be parsed
+    /// together with the errors of all skipped parts. If the type or subtype
+    /// is invalid no media type is returned.
+    ///
+    /// The returned media type only contains the valid parts of the input,
+    /// if any parameter was skipped its buffer is rebuild from the head and
+    /// the valid parameters, separated by `"; "`.
+    pub fn parse_recovering(input: &str) -> (Option<Self>, Vec<ParserErrorRef>) {
+        let (pres, errors) = match parse_recovering::<S>(input) {
+            Ok(res) => res,
+            Err(err) => return (None, vec![err])
+        };
+        let media_type: AnyMediaType = if errors.is_empty() {
+            pres.into()
+        } else {
+            let (buffer, params) = rebuild_buffer(&pres);
+            ParseResult { input: &buffer, params, ..pres }.into()
+        };
+        (Some(MediaType { inner: media_type, _spec: PhantomData }), errors)
+    }
+
     pub fn validate(input: &str) -> bool {
         validate::<S>(input)
     }
 
+    /// checks if the media type is valid under the spec `S2` and if so converts it
+    ///
+    /// Unlike the infallible `From` conversions (e.g. `StrictSpec` => `AnySpec`) this
+    /// works for all specs by re-parsing the media type with `S2`. If it's not valid
+    /// under `S2` the error contains the unchanged media type.
+    pub fn revalidate_as<S2: Spec>(self) -> Result<MediaType<S2>, RevalidationError<S>> {
+        let error = match parse::<S2>(self.as_str_repr()) {
+            Ok(_) => None,
+            Err(err) => Some(err.to_owned())
+        };
+        match error {
+            None => Ok(MediaType { inner: self.inner, _spec: PhantomData }),
+            Some(error) => Err(RevalidationError { media_type: self, error: Box::new(error) })
+        }
+    }
+
+    /// rebuilds the media type so that it's valid under the spec `S2`
+    ///
+    /// The media type is re-generated from it's type, subtype and decoded parameters,
+    /// which drops comments and folding white space, merges RFC 2231 continuations and
+    /// quotes values only where needed. Values which can not be represented under `S2`
+    /// (e.g. non us-ascii values in a http media type) are percent encoded as
+    /// `name*=utf-8''..`. Like with `set_param` values might be split into continuations
+    /// if needed by `S2`.
+    ///
+    /// # Error
+    ///
+    /// Returns an error if the type, subtype or a parameter name is not valid under `S2`,
+    /// the parameters of this media type can not be decoded (see `decoded_params`) or
+    /// a value can not be represented under `S2` (e.g. `StrictSpec` allows neither
+    /// non us-ascii chars nor extended parameters).
+    pub fn normalize_into<S2: Spec>(&self) -> Result<MediaType<S2>, Error> {
+        let (mut buffer, slash_idx, plus_idx, end_of_type) =
+            create_buffer_from::<S2>(self.type_().as_str(), self.subtype().as_str())?;
+        let mut params = Vec::new();
+        for param in self.decoded_params()? {
+            let name = param.name().as_str();
+            match *param.value() {
+                DecodedValue::Plain(ref value) => {
+                    push_param_to_buffer::<S2>(&mut buffer, name, value, &mut params)?;
+                },
+                DecodedValue::Extended(ref value) => {
+                    push_extended_param_to_buffer::<S2>(&mut buffer, name, value, &mut params)?;
+                }
+            }
+        }
+        // quoting and parsing of a spec don't always agree (e.g. `HttpSpec<Obs>` quotes
+        // `"` as `\"` but doesn't accept it when parsing), so the result is re-checked
+        parse::<S2>(&buffer).map_err(|err| err.to_owned())?;
+        Ok(MediaType {
+            inner: AnyMediaType { buffer, slash_idx, plus_idx, end_of_type, params },
+            _spec: PhantomData
+        })
+    }
+
+    /// parses the media type from a complete `Content-Type` header line
+    ///
+    /// The header name is compared case-insensitive, the value can be followed
+    /// by a `"\r\n"` and is unfolded with `S::unfold` before parsing. Returned is
+    /// the media type and the index in `line` at which the value starts.
+    ///
+    /// # Error
+    ///
+    /// The input of the error is the line with the unfolded value, so error
+    /// positions in the value are relative to the unfolded value.
+    pub fn parse_header_line(line: &str) -> Result<(Self, usize), Error> {
+        let (value_start, value_end) = split_header_line(line, CONTENT_TYPE)?;
+        let mut unfolded_line = String::from(&line[..value_start]);
+        unfolded_line.push_str(&S::unfold(&line[value_start..value_end]));
+        let media_type: AnyMediaType = parse::<S>(&unfolded_line[value_start..])
+            .map_err(|err| err.rebased(&unfolded_line, value_start).to_owned())?
+            .into();
+        Ok((MediaType { inner: media_type, _spec: PhantomData }, value_start))
+    }
+
     pub fn new<T, ST>(type_: T, subtype: ST) -> Result<Self, Error>
         where T: AsRef<str>, ST: AsRef<str>
     {
-        let (buffer, slash_idx, end_of_type) =
+        let (buffer, slash_idx, plus_idx, end_of_type) =
             create_buffer_from::<S>(type_.as_ref(), subtype.as_ref())?;
         Ok(MediaType {
             inner: AnyMediaType {
                 buffer,
                 slash_idx,
+                plus_idx,
                 end_of_type,
                 params: Vec::new()
             },
@@ -61,7 +176,7 @@ impl<S> MediaType<S>
               IN: AsRef<str>,
               IV: AsRef<str> //<- we would want something here which can take a Value
     {
-        let (mut buffer, slash_idx, end_of_type) =
+        let (mut buffer, slash_idx, plus_idx, end_of_type) =
             create_buffer_from::<S>(type_.as_ref(), subtype.as_ref())?;
 
         let param_indices =
@@ -71,6 +186,7 @@ impl<S> MediaType<S>
             inner: AnyMediaType {
                 buffer,
                 slash_idx,
+                plus_idx,
                 end_of_type,
                 params: param_indices,
             },
@@ -89,52 +205,82 @@ impl<S> MediaType<S>
     pub fn remove_param<N>(&mut self, name: N) -> bool
         where N: for<'a> PartialEq<Name<'a>>
     {
-        let mut found = None;
-        let mut previous_end = self.end_of_type;
-        for (idx, indices) in self.params.iter().enumerate() {
-            if name == Name::new_unchecked(&self.buffer[indices.start..indices.eq_idx]) {
-                // indices.start is > previous_end, previous_end is before the
-                // ; of the next param, indices.start is after, as we want to
-                // remove everything accosiated with the param we use previous_end
-                found = Some((idx, previous_end, indices.end));
-                break;
-            } else {
-                previous_end = indices.end;
-            }
-        }
+        let found = {
+            let buffer = &self.buffer;
+            self.params.iter().position(|indices| {
+                name == Name::new_unchecked(&buffer[indices.start..indices.eq_idx])
+            })
+        };
 
-        if let Some((idx, start, end)) = found {
-            let size_diff = end - start;
-            let mut tail = self.buffer[end..].to_owned();
-            self.buffer.truncate(start);
-            self.buffer.push_str(&*tail);
-            self.params.remove(idx);
-            // idx now points on the first element which needs fixing or the end of the array
-            for old_indices in self.params[idx..].iter_mut() {
-                old_indices.start -= size_diff;
-                old_indices.eq_idx -= size_diff;
-                old_indices.end -= size_diff;
-            }
+        if let Some(idx) = found {
+            self.remove_param_at(idx);
             true
         } else {
             false
         }
     }
 
-    //TODO handle encodeing (parameters ending in *)
+    /// removes the parameter at the given index in `self.params`
+    fn remove_param_at(&mut self, idx: usize) {
+        // indices.start is > previous_end, previous_end is before the
+        // ; of the next param, indices.start is after, as we want to
+        // remove everything accosiated with the param we use previous_end
+        let start = if idx == 0 { self.end_of_type } else { self.params[idx-1].end };
+        let end = self.params[idx].end;
+        let size_diff = end - start;
+        let tail = self.buffer[end..].to_owned();
+        self.buffer.truncate(start);
+        self.buffer.push_str(&tail);
+        self.params.remove(idx);
+        // idx now points on the first element which needs fixing or the end of the array
+        for old_indices in self.params[idx..].iter_mut() {
+            old_indices.start -= size_diff;
+            old_indices.eq_idx -= size_diff;
+            old_indices.end -= size_diff;
+        }
+    }
+
+    /// removes all parameters which are (sections of) the logical parameter `name`
+    ///
+    /// I.e. `name`, `name*`, `name*0`, `name*1*` etc.
+    fn remove_all_sections_of(&mut self, name: &str) {
+        loop {
+            let found = {
+                let buffer = &self.buffer;
+                self.params.iter().position(|indices| {
+                    let (base, _, _) = split_section_name(&buffer[indices.start..indices.eq_idx]);
+                    base.eq_ignore_ascii_case(name)
+                })
+            };
+            match found {
+                Some(idx) => self.remove_param_at(idx),
+                None => break
+            }
+        }
+    }
+
     /// set a given parameter to a give value, overriding the old parameter
     ///
     /// If there already exists a parameter with the same name the
-    /// parameter is overridden.
+    /// parameter is overridden, this includes encoded (`name*`) and
+    /// continued (`name*0`, `name*1`, ...) parameters.
     ///
     /// If there the parameter is not part of the media type it is added.
     ///
+    /// If needed the value is quoted or encoded, if the parameter is longer then
+    /// `S::param_split_threshold()` it is split into RFC 2231 continuations.
+    ///
     /// Note that parameters are order-independent given rfc2045, as such
     /// the order parameters will have after this function was used is
     /// implementation dependent and can change. Mainly this means that
     /// this function _could_ replace the parameter in place or _could_
     /// remove it and add the new parameter the end or insert it in the
     /// beginning.
+    ///
+    /// # Panics
+    ///
+    /// If the value needs to be encoded but `S` doesn't allow extended
+    /// parameters (e.g. a non us-ascii value with `StrictSpec`).
     pub fn set_param<N, V>(&mut self, name: N, value: V)
         where N: AsRef<str>, V: AsRef<str>
     {
@@ -143,22 +289,96 @@ impl<S> MediaType<S>
         //   overwrite_slice(&mut String, Slice, W) where FnOnce(&mut Writer) or so
         let name = name.as_ref();
         let value = value.as_ref();
-        self.remove_param(name);
-        let indices =
-            push_param_to_buffer::<S>(&mut self.buffer, name, value)
-                .expect("[BUG] parameter name matched existing parameter but was also invalid");
-        self.params.push(indices);
+        self.remove_all_sections_of(name);
+        let inner = &mut self.inner;
+        push_param_to_buffer::<S>(&mut inner.buffer, name, value, &mut inner.params)
+            .expect("parameter name or encoded value is not valid under the spec");
     }
+
+    /// set a given parameter to a given extended value, overriding the old parameter
+    ///
+    /// The parameter is written in its encoded form `name*=charset'language'value`,
+    /// like with `set_param` any existing (encoded/continued) parameter with the
+    /// same name is removed.
+    ///
+    /// See `set_param` for details about the parameter order.
+    ///
+    /// # Error
+    ///
+    /// An error is returned if the charset or language of the value are not
+    /// valid, e.g. because the value was decoded from a media type parsed with
+    /// a more lenient spec, or if `S` does not allow extended parameters (see
+    /// `push_extended_param_to_buffer`). In that case the media type is not changed.
+    pub fn set_extended_param<N>(&mut self, name: N, value: &ExtendedValue) -> Result<(), Error>
+        where N: AsRef<str>
+    {
+        let name = name.as_ref();
+        value.validate()?;
+        let (encoded, prefix_len) = encode_extended_value::<S>(name, value)?;
+        self.remove_all_sections_of(name);
+        let inner = &mut self.inner;
+        push_encoded_param_to_buffer::<S>(
+            &mut inner.buffer, name, &encoded, prefix_len, &mut inner.params
+        );
+        Ok(())
+    }
+}
+
+/// creates a new buffer containing only the head and the parameters of the parse result
+fn rebuild_buffer(pres: &ParseResult) -> (String, Vec<ParamIndices>) {
+    let mut buffer = String::from(&pres.input[..pres.end_of_type_idx]);
+    let params = pres.params.iter()
+        .map(|indices| {
+            buffer.push_str("; ");
+            let start = buffer.len();
+            buffer.push_str(&pres.input[indices.start..indices.end]);
+            ParamIndices {
+                start,
+                eq_idx: start + (indices.eq_idx - indices.start),
+                end: buffer.len()
+            }
+        })
+        .collect();
+    (buffer, params)
 }
 
+impl<S> MediaType<S>
+    where S: FoldingSpec
+{
+    /// writes the media type folded into lines of at most `max_width` chars
+    ///
+    /// The media type is only folded at parameter boundaries (by replacing
+    /// the white space after the `;` with `"\r\n "`). As such the output
+    /// can still be parsed with the spec `S` but a single parameter longer
+    /// than `max_width` still exceeds it (see `SplitThreshold` for avoiding
+    /// long parameters).
+    ///
+    /// `initial_offset` is the length of everything already written on the
+    /// first line, e.g. `"Content-Type: ".len()`.
+    pub fn write_folded<W>(&self, out: &mut W, initial_offset: usize, max_width: usize) -> fmt::Result
+        where W: fmt::Write
+    {
+        write_folded(
+            out, &self.buffer, self.end_of_type, &self.params, initial_offset, max_width
+        )
+    }
+
+    /// returns a string containing the folded media type, see `write_folded`
+    pub fn to_folded_string(&self, initial_offset: usize, max_width: usize) -> String {
+        let mut out = String::new();
+        self.write_folded(&mut out, initial_offset, max_width)
+            .expect("[BUG] writing to a string failed");
+        out
+    }
+}
 
 macro_rules! conversions {
-    ($($tp:ty => $tp2:ty;)*) => (
+    ($([$($st:ident),*] $tp:ty => $tp2:ty;)*) => (
         mod conversion_impl_ns { $(
             #[allow(unused_imports)]
             use spec::*;
 
-            impl From<$crate::MediaType<$tp>> for $crate::MediaType<$tp2> {
+            impl<$($st: SplitThreshold),*> From<$crate::MediaType<$tp>> for $crate::MediaType<$tp2> {
                 fn from(media_type: $crate::MediaType<$tp>) -> $crate::MediaType<$tp2> {
                     $crate::MediaType {
                         inner: media_type.inner,
@@ -174,25 +394,151 @@ macro_rules! conversions {
 // From<MediaType<S>> for MediaType<S2> where S2: From<S>, currently this
 // won't work due to conflicting implementations
 conversions! {
-    MimeSpec<Ascii, Obs> => MimeSpec<Internationalized, Obs>;
-    MimeSpec<Ascii, Modern> => MimeSpec<Ascii, Obs>;
-    MimeSpec<Ascii, Modern> => MimeSpec<Internationalized, Obs>;
-    MimeSpec<Ascii, Modern> => MimeSpec<Internationalized, Modern>;
-    MimeSpec<Internationalized, Modern> => MimeSpec<Internationalized, Obs>;
-    HttpSpec<Modern> => HttpSpec<Obs>;
-    StrictSpec => HttpSpec<Modern>;
-    StrictSpec => HttpSpec<Obs>;
-    StrictSpec => MimeSpec<Ascii, Obs>;
-    StrictSpec => MimeSpec<Ascii, Modern>;
-    StrictSpec => MimeSpec<Internationalized, Obs>;
-    StrictSpec => MimeSpec<Internationalized, Modern>;
-    StrictSpec => AnySpec;
-    HttpSpec<Modern> => AnySpec;
-    HttpSpec<Obs> => AnySpec;
-    MimeSpec<Ascii, Obs> => AnySpec;
-    MimeSpec<Ascii, Modern> => AnySpec;
-    MimeSpec<Internationalized, Obs> => AnySpec;
-    MimeSpec<Internationalized, Modern> => AnySpec;
+    [] RegistrationSpec => StrictSpec;
+    [] RegistrationSpec => HttpSpec<Modern>;
+    [] RegistrationSpec => HttpSpec<Obs>;
+    [ST] RegistrationSpec => MimeSpec<Ascii, Obs, ST>;
+    [ST] RegistrationSpec => MimeSpec<Ascii, Modern, ST>;
+    [ST] RegistrationSpec => MimeSpec<Internationalized, Obs, ST>;
+    [ST] RegistrationSpec => MimeSpec<Internationalized, Modern, ST>;
+    [] RegistrationSpec => AnySpec;
+    [ST1, ST2] MimeSpec<Ascii, Obs, ST1> => MimeSpec<Internationalized, Obs, ST2>;
+    [ST1, ST2] MimeSpec<Ascii, Modern, ST1> => MimeSpec<Ascii, Obs, ST2>;
+    [ST1, ST2] MimeSpec<Ascii, Modern, ST1> => MimeSpec<Internationalized, Obs, ST2>;
+    [ST1, ST2] MimeSpec<Ascii, Modern, ST1> => MimeSpec<Internationalized, Modern, ST2>;
+    [ST1, ST2] MimeSpec<Internationalized, Modern, ST1> => MimeSpec<Internationalized, Obs, ST2>;
+    [] HttpSpec<Modern> => HttpSpec<Obs>;
+    [] StrictSpec => HttpSpec<Modern>;
+    [] StrictSpec => HttpSpec<Obs>;
+    [ST] StrictSpec => MimeSpec<Ascii, Obs, ST>;
+    [ST] StrictSpec => MimeSpec<Ascii, Modern, ST>;
+    [ST] StrictSpec => MimeSpec<Internationalized, Obs, ST>;
+    [ST] StrictSpec => MimeSpec<Internationalized, Modern, ST>;
+    [] StrictSpec => AnySpec;
+    [] HttpSpec<Modern> => AnySpec;
+    [] HttpSpec<Obs> => AnySpec;
+    [ST] MimeSpec<Ascii, Obs, ST> => AnySpec;
+    [ST] MimeSpec<Ascii, Modern, ST> => AnySpec;
+    [ST] MimeSpec<Internationalized, Obs, ST> => AnySpec;
+    [ST] MimeSpec<Internationalized, Modern, ST> => AnySpec;
+}
+
+macro_rules! try_conversions {
+    ($([$($st:ident),*] $tp:ty => $tp2:ty;)*) => (
+        mod try_conversion_impl_ns { $(
+            #[allow(unused_imports)]
+            use spec::*;
+
+            impl<$($st: SplitThreshold),*> ::std::convert::TryFrom<$crate::MediaType<$tp>>
+                for $crate::MediaType<$tp2>
+            {
+                type Error = $crate::RevalidationError<$tp>;
+
+                fn try_from(media_type: $crate::MediaType<$tp>)
+                    -> Result<$crate::MediaType<$tp2>, Self::Error>
+                {
+                    media_type.revalidate_as()
+                }
+            }
+        )* }
+    );
+}
+
+// all spec pairs which have no `From` impl (see `conversions!`)
+try_conversions! {
+    [] StrictSpec => RegistrationSpec;
+    [] HttpSpec<Modern> => StrictSpec;
+    [] HttpSpec<Modern> => RegistrationSpec;
+    [ST] HttpSpec<Modern> => MimeSpec<Ascii, Obs, ST>;
+    [ST] HttpSpec<Modern> => MimeSpec<Ascii, Modern, ST>;
+    [ST] HttpSpec<Modern> => MimeSpec<Internationalized, Obs, ST>;
+    [ST] HttpSpec<Modern> => MimeSpec<Internationalized, Modern, ST>;
+    [] HttpSpec<Obs> => StrictSpec;
+    [] HttpSpec<Obs> => RegistrationSpec;
+    [] HttpSpec<Obs> => HttpSpec<Modern>;
+    [ST] HttpSpec<Obs> => MimeSpec<Ascii, Obs, ST>;
+    [ST] HttpSpec<Obs> => MimeSpec<Ascii, Modern, ST>;
+    [ST] HttpSpec<Obs> => MimeSpec<Internationalized, Obs, ST>;
+    [ST] HttpSpec<Obs> => MimeSpec<Internationalized, Modern, ST>;
+    [ST] MimeSpec<Ascii, Obs, ST> => StrictSpec;
+    [ST] MimeSpec<Ascii, Obs, ST> => RegistrationSpec;
+    [ST] MimeSpec<Ascii, Obs, ST> => HttpSpec<Modern>;
+    [ST] MimeSpec<Ascii, Obs, ST> => HttpSpec<Obs>;
+    [ST1, ST2] MimeSpec<Ascii, Obs, ST1> => MimeSpec<Ascii, Modern, ST2>;
+    [ST1, ST2] MimeSpec<Ascii, Obs, ST1> => MimeSpec<Internationalized, Modern, ST2>;
+    [ST] MimeSpec<Ascii, Modern, ST> => StrictSpec;
+    [ST] MimeSpec<Ascii, Modern, ST> => RegistrationSpec;
+    [ST] MimeSpec<Ascii, Modern, ST> => HttpSpec<Modern>;
+    [ST] MimeSpec<Ascii, Modern, ST> => HttpSpec<Obs>;
+    [ST] MimeSpec<Internationalized, Obs, ST> => StrictSpec;
+    [ST] MimeSpec<Internationalized, Obs, ST> => RegistrationSpec;
+    [ST] MimeSpec<Internationalized, Obs, ST> => HttpSpec<Modern>;
+    [ST] MimeSpec<Internationalized, Obs, ST> => HttpSpec<Obs>;
+    [ST1, ST2] MimeSpec<Internationalized, Obs, ST1> => MimeSpec<Ascii, Obs, ST2>;
+    [ST1, ST2] MimeSpec<Internationalized, Obs, ST1> => MimeSpec<Ascii, Modern, ST2>;
+    [ST1, ST2] MimeSpec<Internationalized, Obs, ST1> => MimeSpec<Internationalized, Modern, ST2>;
+    [ST] MimeSpec<Internationalized, Modern, ST> => StrictSpec;
+    [ST] MimeSpec<Internationalized, Modern, ST> => RegistrationSpec;
+    [ST] MimeSpec<Internationalized, Modern, ST> => HttpSpec<Modern>;
+    [ST] MimeSpec<Internationalized, Modern, ST> => HttpSpec<Obs>;
+    [ST1, ST2] MimeSpec<Internationalized, Modern, ST1> => MimeSpec<Ascii, Obs, ST2>;
+    [ST1, ST2] MimeSpec<Internationalized, Modern, ST1> => MimeSpec<Ascii, Modern, ST2>;
+    [] AnySpec => StrictSpec;
+    [] AnySpec => RegistrationSpec;
+    [] AnySpec => HttpSpec<Modern>;
+    [] AnySpec => HttpSpec<Obs>;
+    [ST] AnySpec => MimeSpec<Ascii, Obs, ST>;
+    [ST] AnySpec => MimeSpec<Ascii, Modern, ST>;
+    [ST] AnySpec => MimeSpec<Internationalized, Obs, ST>;
+    [ST] AnySpec => MimeSpec<Internationalized, Modern, ST>;
+}
+
+/// The error returned if a media type is not valid under the spec it's converted to
+///
+/// Contains the unchanged media type and the error of parsing it with the other spec.
+#[derive(Clone, Debug)]
+pub struct RevalidationError<S: Spec> {
+    media_type: MediaType<S>,
+    // boxed to keep `Result`s with this error small
+    error: Box<Error>
+}
+
+impl<S> RevalidationError<S>
+    where S: Spec
+{
+    /// the error of parsing the media type with the other spec
+    pub fn error(&self) -> &Error {
+        &self.error
+    }
+
+    /// the media type which failed to convert
+    pub fn media_type(&self) -> &MediaType<S> {
+        &self.media_type
+    }
+
+    pub fn into_media_type(self) -> MediaType<S> {
+        self.media_type
+    }
+}
+
+impl<S> Display for RevalidationError<S>
+    where S: Spec
+{
+    fn fmt(&self, fter: &mut fmt::Formatter) -> fmt::Result {
+        Display::fmt(&self.error, fter)
+    }
+}
+
+impl<S> StdError for RevalidationError<S>
+    where S: Spec
+{
+    fn description(&self) -> &str {
+        "media type is not valid under the spec it's converted to"
+    }
+
+    fn cause(&self) -> Option<&dyn StdError> {
+        Some(&*self.error)
+    }
 }
 
 impl<S1, S2> PartialEq<MediaType<S2>> for MediaType<S1>
@@ -204,6 +550,34 @@ impl<S1, S2> PartialEq<MediaType<S2>> for MediaType<S1>
     }
 }
 
+impl<S> Eq for MediaType<S>
+    where S: Spec
+{}
+
+impl<S1, S2> PartialOrd<MediaType<S2>> for MediaType<S1>
+    where S1: Spec, S2: Spec
+{
+    fn partial_cmp(&self, other: &MediaType<S2>) -> Option<Ordering> {
+        Some(self.inner.cmp(&other.inner))
+    }
+}
+
+impl<S> Ord for MediaType<S>
+    where S: Spec
+{
+    fn cmp(&self, other: &MediaType<S>) -> Ordering {
+        self.inner.cmp(&other.inner)
+    }
+}
+
+impl<S> Hash for MediaType<S>
+    where S: Spec
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.inner.hash(state)
+    }
+}
+
 impl<S> Deref for MediaType<S>
     where S: Spec
 {
@@ -251,7 +625,7 @@ pub struct AnyMediaType {
     buffer: String,
     slash_idx: usize,
     /// is equal the end_type_idx if there is no plus
-    //plus_idx: usize,
+    plus_idx: usize,
     /// it is the index behind the last character of the subtype(inkl. suffix) which is equal to the
     /// index of the ";" of the first parameter or the len of the buffer if there are no parameter
     end_of_type: usize,
@@ -260,28 +634,74 @@ pub struct AnyMediaType {
 
 impl AnyMediaType {
 
+    /// parses the input with the WHATWG "parse a MIME type" algorithm
+    ///
+    /// This is the lenient parsing browsers use: stray white space and
+    /// empty parameters are ignored, invalid parameters are skipped instead
+    /// of causing an error and if a parameter appears multiple times the
+    /// first one is used. The buffer of the returned media type is the
+    /// WHATWG serialization of it (see `to_whatwg_string`).
+    ///
+    /// # Error
+    ///
+    /// An error is returned if the type or subtype is empty or contains
+    /// chars which are not http token chars.
+    pub fn parse_whatwg(input: &str) -> Result<AnyMediaType, ParserErrorRef> {
+        let whatwg::WhatwgParts { buffer, slash_idx, end_of_type, params } = whatwg::parse(input)?;
+        let plus_idx = find_plus_idx(&buffer, slash_idx, end_of_type);
+        Ok(AnyMediaType { buffer, slash_idx, plus_idx, end_of_type, params })
+    }
+
+    /// serializes the media type with the WHATWG "serialize a MIME type" algorithm
+    ///
+    /// I.e. `type/subtype` followed by `;name=value` for each parameter,
+    /// where the value is quoted if it's empty or not a http token.
+    pub fn to_whatwg_string(&self) -> String {
+        let mut buffer = String::from(self.full_type().as_str());
+        for (name, value) in self.params() {
+            whatwg::push_param(&mut buffer, name.as_str(), &value.to_content());
+        }
+        buffer
+    }
+
     pub fn type_(&self) -> Name {
         Name::new_unchecked(&self.buffer[..self.slash_idx])
     }
 
+    /// the subtype including the suffix, e.g. `svg+xml` for `image/svg+xml`
     pub fn subtype(&self) -> Name {
         Name::new_unchecked(&self.buffer[self.slash_idx+1..self.end_of_type])
-        //Name::new_unchecked(&self.buffer[self.slash_idx+1..self.plus_idx])
+    }
+
+    /// the subtype without the suffix, e.g. `svg` for `image/svg+xml`
+    ///
+    /// If there is no suffix this is the same as `subtype`.
+    pub fn subtype_without_suffix(&self) -> Name {
+        Name::new_unchecked(&self.buffer[self.slash_idx+1..self.plus_idx])
+    }
+
+    /// the registration tree of the media type, determined by `subtype_without_suffix`
+    pub fn tree(&self) -> RegistrationTree {
+        RegistrationTree::from_subtype(self.subtype_without_suffix().as_str())
     }
 
     pub fn full_type(&self) -> Name {
         Name::new_unchecked(&self.buffer[..self.end_of_type])
     }
 
-//    pub fn suffix(&self) -> Option<Name> {
-//        let suffix_start = self.plus_idx+1;
-//        let end_idx = self.end_of_type;
-//        if suffix_start < end_idx {
-//            Some(Name::new_unchecked(&self.buffer[suffix_start..end_idx]))
-//        } else {
-//            None
-//        }
-//    }
+    /// the structured syntax suffix (RFC 6838 section 4.2.8), e.g. `xml` for `image/svg+xml`
+    ///
+    /// The suffix is the part of the subtype after the last `+`, if the `+` is
+    /// the first or last char of the subtype there is no suffix.
+    pub fn suffix(&self) -> Option<Name> {
+        let suffix_start = self.plus_idx+1;
+        let end_idx = self.end_of_type;
+        if suffix_start < end_idx {
+            Some(Name::new_unchecked(&self.buffer[suffix_start..end_idx]))
+        } else {
+            None
+        }
+    }
 
     pub fn get_param<'a, N>(&'a self, attr: N) -> Option<Value<'a>>
         where N: PartialEq<Name<'a>>
@@ -298,15 +718,110 @@ impl AnyMediaType {
         }
     }
 
+    /// returns the parameters with RFC 2231 continuations and encodings resolved
+    ///
+    /// In difference to `params` parameter sections like `name*0*=..; name*1=..`
+    /// are merged into exactly one logical parameter named `name` and
+    /// extended (`*`-suffixed) values are percent-decoded.
+    ///
+    /// # Error
+    ///
+    /// An error is returned if the sections of a continued parameter are out of order,
+    /// missing or duplicated or if an extended value lacks the `charset'language'` prefix.
+    pub fn decoded_params(&self) -> Result<DecodedParams, ParserErrorRef> {
+        DecodedParams::new(self.buffer.as_str(), &self.params)
+    }
+
+    /// returns the decoded value of the logical parameter with the given name
+    ///
+    /// See `decoded_params` for more details.
+    pub fn get_decoded_param<'a, N>(&'a self, attr: N) -> Result<Option<DecodedValue<'a>>, ParserErrorRef<'a>>
+        where N: PartialEq<Name<'a>>
+    {
+        Ok(self.decoded_params()?
+            .find(|param| attr == param.name())
+            .map(|param| param.into_value()))
+    }
+
     pub fn as_str_repr(&self) -> &str {
         self.buffer.as_str()
     }
 
+    /// returns the canonical form of the media type
+    ///
+    /// In the canonical form type, subtype and parameter names are lowercase,
+    /// the parameters are sorted by name (and value), values are only quoted
+    /// if needed and comments and white space (except the `"; "` before each
+    /// parameter) are removed. Values of the parameters in `CASE_INSENSITIVE_PARAMS`
+    /// (i.e. `charset`) are lowercased and a `utf8` charset is replaced by `utf-8`.
+    ///
+    /// Two media types are equal if and only if their canonical forms are equal,
+    /// which makes `canonical().as_str_repr()` usable e.g. as a cache key.
+    pub fn canonical(&self) -> AnyMediaType {
+        let mut buffer = self.full_type().to_ascii_lowercase();
+        let slash_idx = self.slash_idx;
+        let end_of_type = buffer.len();
+        let plus_idx = find_plus_idx(&buffer, slash_idx, end_of_type);
+        let mut params = Vec::with_capacity(self.params.len());
+        for (name, value) in self.canonical_params() {
+            push_param_to_buffer::<AnySpec>(&mut buffer, &name, &value, &mut params)
+                .expect("[BUG] valid parameter name is not valid under AnySpec");
+        }
+        AnyMediaType { buffer, slash_idx, plus_idx, end_of_type, params }
+    }
+
+    /// like `canonical_params` but only for up to two parameters, without collecting them
+    ///
+    /// Returns `None` if the media type has more then two parameters.
+    fn few_canonical_params(&self) -> Option<[Option<CanonicalParam>; 2]> {
+        if self.params.len() > 2 {
+            return None;
+        }
+        let mut params = self.params().map(canonical_param);
+        let (param1, param2) = (params.next(), params.next());
+        if param2.is_some() && param2 < param1 {
+            Some([param2, param1])
+        } else {
+            Some([param1, param2])
+        }
+    }
+
+    /// like `==` but also compares the values of the parameters in `case_insensitive` case-insensitive
+    ///
+    /// The parameters in `CASE_INSENSITIVE_PARAMS` are always compared case-insensitive,
+    /// `case_insensitive` adds further parameters, e.g. ones whose registration defines
+    /// their values as case-insensitive. Parameter names are matched case-insensitive.
+    pub fn eq_with_case_insensitive(&self, other: &AnyMediaType, case_insensitive: &[&str]) -> bool {
+        self.type_().eq_ignore_ascii_case(&other.type_())
+            && self.subtype().eq_ignore_ascii_case(&other.subtype())
+            && self.params.len() == other.params.len()
+            && self.canonical_params_with(case_insensitive) == other.canonical_params_with(case_insensitive)
+    }
+
+    /// returns the lowercased names and canonical values of all parameters sorted by name and value
+    fn canonical_params(&self) -> Vec<(Cow<str>, Cow<str>)> {
+        self.canonical_params_with(&[])
+    }
+
+    /// like `canonical_params` but also lowercases the values of the parameters in `case_insensitive`
+    fn canonical_params_with(&self, case_insensitive: &[&str]) -> Vec<(Cow<str>, Cow<str>)> {
+        let mut params = self.params()
+            .map(|param| {
+                let (name, mut value) = canonical_param(param);
+                if case_insensitive.iter().any(|ci_name| str::eq_ignore_ascii_case(ci_name, &name)) {
+                    value = lowercase_cow(value);
+                }
+                (name, value)
+            })
+            .collect::<Vec<_>>();
+        params.sort();
+        params
+    }
+
     pub fn has_utf8_charset(&self) -> bool {
         self.get_param(CHARSET)
             .map(|cs_param| {
-                //FIXME use eq_ascii_case_insensitive
-                cs_param == UTF_8 || cs_param == UTF8
+                cs_param.eq_ignore_ascii_case(&UTF_8) || cs_param.eq_ignore_ascii_case(&UTF8)
             })
             .unwrap_or(false)
     }
@@ -318,56 +833,96 @@ impl AnyMediaType {
 }
 
 
+/// Two media types are equal if their canonical forms are equal (see `AnyMediaType::canonical`)
 impl PartialEq for AnyMediaType {
     fn eq(&self, other: &AnyMediaType) -> bool {
-        if self.type_() != other.type_()
-            || self.subtype() != other.subtype()
-            //|| self.suffix() != other.suffix()
+        if !self.type_().eq_ignore_ascii_case(&other.type_())
+            || !self.subtype().eq_ignore_ascii_case(&other.subtype())
+            || self.params.len() != other.params.len()
         {
             return false;
-        } else {
-            let len = self.params.len();
-            let other_len = other.params.len();
-            if len != other_len { return false; }
-            match len {
-                0 => true,
-
-                //OPTIMIZATION: most media types have very little parameter, so we can avoid
-                // the "costy order independent comparsion" for them
-                1 => {
-                    let (name, value) = self.params().next().unwrap();
-                    let (other_name, other_value) = other.params().next().unwrap();
-                    return name == other_name && value == other_value
-                },
-                2 => {
-                    let mut params = self.params();
-                    let mut other_params = other.params();
-                    let (name1, value1) = params.next().unwrap();
-                    let (other_name1, other_value1) = other_params.next().unwrap();
-                    let (name2, value2) = params.next().unwrap();
-                    let (other_name2, other_value2) = other_params.next().unwrap();
-                    if name1 == other_name1 {
-                        return value1 == other_value1
-                            && name2 == other_name2 && value2 == other_value2
-                    } else {
-                        return
-                            name1 == other_name2 && value1 == other_value2
-                                && name2 == other_name1 && value2 == other_value1
-                    }
-                },
-                _ => {
-                    //TODO Optimized use on stack map, sort compare?
-                    let map = self.params().collect::<HashMap<_, _>>();
-                    // we already checked that the len of both is the same
-                    // so if all params of other are in map they are equal
-                    other.params()
-                        .all(|(other_name, other_value)| {
-                            map.get(&other_name)
-                                .map(|value| other_value == *value)
-                                .unwrap_or(false)
-                        })
+        }
+        match self.params.len() {
+            0 => true,
+
+            //OPTIMIZATION: most media types have very little parameter, so we can avoid
+            // collecting and sorting the parameters for them
+            1 => self.params().map(canonical_param).eq(other.params().map(canonical_param)),
+            2 => {
+                let mut params = self.params().map(canonical_param);
+                let mut other_params = other.params().map(canonical_param);
+                let (param1, param2) = (params.next(), params.next());
+                let (other_param1, other_param2) = (other_params.next(), other_params.next());
+                (param1 == other_param1 && param2 == other_param2)
+                    || (param1 == other_param2 && param2 == other_param1)
+            },
+            _ => self.canonical_params() == other.canonical_params()
+        }
+    }
+}
+
+impl Eq for AnyMediaType {}
+
+/// Orders media types by type, subtype and then their canonical parameters
+///
+/// Type and subtype are compared ascii case-insensitive, the parameters are compared
+/// in the order of the canonical form (see `AnyMediaType::canonical`), i.e. sorted by
+/// name and value, comparing the content of the values.
+impl Ord for AnyMediaType {
+    fn cmp(&self, other: &AnyMediaType) -> Ordering {
+        cmp_ignore_ascii_case(&self.type_(), &other.type_())
+            .then_with(|| cmp_ignore_ascii_case(&self.subtype(), &other.subtype()))
+            .then_with(|| {
+                //OPTIMIZATION: like with `eq` avoid collecting and sorting the
+                // parameters if both media types have only few of them
+                match (self.few_canonical_params(), other.few_canonical_params()) {
+                    (Some(params), Some(other_params)) => {
+                        params.iter().flatten().cmp(other_params.iter().flatten())
+                    },
+                    _ => self.canonical_params().cmp(&other.canonical_params())
                 }
-            }
+            })
+    }
+}
+
+impl PartialOrd for AnyMediaType {
+    fn partial_cmp(&self, other: &AnyMediaType) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+fn cmp_ignore_ascii_case(left: &str, right: &str) -> Ordering {
+    let left = left.bytes().map(|bch| bch.to_ascii_lowercase());
+    let right = right.bytes().map(|bch| bch.to_ascii_lowercase());
+    left.cmp(right)
+}
+
+/// Hashes the canonical form, so that equal media types have the same hash
+impl Hash for AnyMediaType {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for bch in self.full_type().bytes() {
+            state.write_u8(bch.to_ascii_lowercase());
+        }
+        // like `str` write a byte which can not appear in the type to separate it from the params
+        state.write_u8(0xff);
+        match self.params.len() {
+            0 => {},
+
+            //OPTIMIZATION: like with `eq` avoid collecting and sorting the parameters
+            // if there are only few of them, the order still can't affect the hash
+            1 => self.params().map(canonical_param).for_each(|param| param.hash(state)),
+            2 => {
+                let mut params = self.params().map(canonical_param);
+                let (param1, param2) = (params.next(), params.next());
+                if param1 <= param2 {
+                    param1.hash(state);
+                    param2.hash(state);
+                } else {
+                    param2.hash(state);
+                    param1.hash(state);
+                }
+            },
+            _ => self.canonical_params().hash(state)
         }
     }
 }
@@ -393,6 +948,7 @@ impl<'a> From<ParseResult<'a>> for AnyMediaType {
         AnyMediaType {
             buffer,
             slash_idx: pres.slash_idx,
+            plus_idx: pres.plus_idx,
             end_of_type: pres.end_of_type_idx,
             params: pres.params
         }
@@ -408,12 +964,66 @@ impl Display for AnyMediaType {
 
 
 
+fn lowercase(input: &str) -> Cow<str> {
+    if input.bytes().any(|bch| bch.is_ascii_uppercase()) {
+        Cow::Owned(input.to_ascii_lowercase())
+    } else {
+        Cow::Borrowed(input)
+    }
+}
+
+/// a parameter as returned by `canonical_param`
+type CanonicalParam<'a> = (Cow<'a, str>, Cow<'a, str>);
+
+/// the lowercased name and canonical value of a parameter
+fn canonical_param<'a>((name, value): (Name<'a>, Value<'a>)) -> (Cow<'a, str>, Cow<'a, str>) {
+    let name = lowercase(name.as_str());
+    let value = canonical_value(&name, value);
+    (name, value)
+}
+
+fn lowercase_cow(input: Cow<str>) -> Cow<str> {
+    match input {
+        Cow::Borrowed(input) => lowercase(input),
+        Cow::Owned(input) => Cow::Owned(input.to_ascii_lowercase())
+    }
+}
+
+/// the content of the value, lowercased if the parameter is in `CASE_INSENSITIVE_PARAMS`
+///
+/// Additionally a `utf8` charset is mapped to `utf-8`. `name` has to be lowercase.
+fn canonical_value<'a>(name: &str, value: Value<'a>) -> Cow<'a, str> {
+    let content = value.to_content();
+    if !CASE_INSENSITIVE_PARAMS.iter().any(|ci_name| ci_name.as_str() == name) {
+        return content;
+    }
+    let content = lowercase_cow(content);
+    if name == CHARSET.as_str() && content == UTF8.as_str_repr() {
+        Cow::Borrowed(UTF_8.as_str_repr())
+    } else {
+        content
+    }
+}
+
+/// compares the values of the parameter `name` the same way `AnyMediaType`'s `PartialEq` does
+pub(crate) fn param_values_eq(name: Name, left: Value, right: Value) -> bool {
+    let name = lowercase(name.as_str());
+    canonical_value(&name, left) == canonical_value(&name, right)
+}
+
 #[derive(Clone)]
 pub struct Params<'a> {
     source: &'a str,
     iter: slice::Iter<'a, ParamIndices>
 }
 
+impl<'a> Params<'a> {
+
+    pub(crate) fn new(source: &'a str, params: &'a [ParamIndices]) -> Self {
+        Params { source, iter: params.iter() }
+    }
+}
+
 impl<'a> Iterator for Params<'a> {
     type Item = (Name<'a>, Value<'a>);
 
@@ -525,6 +1135,548 @@ mod test {
         assert_eq!(mt1, mt2);
     }
 
+    mod parse_recovering {
+        use super::super::MediaType;
+        use parse::{HttpSpec, MimeSpec, Modern};
+
+        #[test]
+        fn valid_input() {
+            let input = "text/plain; charset=utf-8";
+            let (mt, errors) = MediaType::<HttpSpec<Modern>>::parse_recovering(input);
+            let mt = mt.unwrap();
+            assert!(errors.is_empty());
+            assert_eq!(mt.as_str_repr(), input);
+        }
+
+        #[test]
+        fn skips_invalid_params() {
+            let (mt, errors) = MediaType::<HttpSpec<Modern>>::parse_recovering(
+                "Text/Plain;Charset=utf-8; a b; c=\"d;e\"; =x;f=g;"
+            );
+            let mt = mt.unwrap();
+            assert_eq!(errors.len(), 3);
+            assert_eq!(mt.as_str_repr(), "text/plain; charset=utf-8; c=\"d;e\"; f=g");
+            assert_eq!(mt.get_param("c").unwrap().to_content(), "d;e");
+            assert_eq!(mt.get_param("f").unwrap(), "g");
+            assert_eq!(mt.params().len(), 3);
+            assert!(MediaType::<HttpSpec<Modern>>::validate(mt.as_str_repr()));
+        }
+
+        #[test]
+        fn invalid_type() {
+            let (mt, errors) = MediaType::<MimeSpec>::parse_recovering("text/; a=b");
+            assert!(mt.is_none());
+            assert_eq!(errors.len(), 1);
+        }
+    }
+
+    mod revalidate {
+        use std::convert::TryFrom;
+        use super::super::MediaType;
+        use parse::{AnySpec, HttpSpec, MimeSpec, Ascii, Modern, Obs, NoSplit, StrictSpec};
+
+        #[test]
+        fn valid_under_other_spec() {
+            let mt = MediaType::<AnySpec>::parse("text/plain; charset=utf-8").unwrap();
+            let mt: MediaType<HttpSpec<Modern>> = mt.revalidate_as().unwrap();
+            assert_eq!(mt.as_str_repr(), "text/plain; charset=utf-8");
+            let mt = MediaType::<StrictSpec>::try_from(mt).unwrap();
+            assert_eq!(mt.get_param("charset").unwrap(), "utf-8");
+        }
+
+        #[test]
+        fn invalid_under_other_spec() {
+            let input = "text/plain; (comment) charset=utf-8";
+            let mt = MediaType::<MimeSpec>::parse(input).unwrap();
+            let err = MediaType::<HttpSpec<Obs>>::try_from(mt).unwrap_err();
+            assert_eq!(err.error().input(), input);
+            assert_eq!(err.into_media_type().as_str_repr(), input);
+        }
+
+        #[test]
+        fn no_split_mime_spec() {
+            let mt = MediaType::<HttpSpec<Obs>>::parse("text/plain; a=b").unwrap();
+            let mt = MediaType::<MimeSpec<Ascii, Modern, NoSplit>>::try_from(mt).unwrap();
+            let mt = MediaType::<MimeSpec<Ascii, Obs>>::try_from(
+                MediaType::<HttpSpec<Modern>>::try_from(mt).unwrap()
+            ).unwrap();
+            assert_eq!(mt.as_str_repr(), "text/plain; a=b");
+        }
+
+        #[test]
+        fn revalidate_as_same_spec() {
+            let mt = MediaType::<MimeSpec>::parse("text/plain; a=b").unwrap();
+            assert!(mt.revalidate_as::<MimeSpec>().is_ok());
+        }
+    }
+
+    mod normalize_into {
+        use super::super::MediaType;
+        use spec::Spec;
+        use parse::{
+            AnySpec, HttpSpec, MimeSpec, Ascii, Modern, Obs, NoSplit,
+            StrictSpec, RegistrationSpec
+        };
+
+        #[test]
+        fn mail_to_http() {
+            let mt = MediaType::<AnySpec>::parse(
+                "Text/Plain; (a comment)\r\n charset=\"utf-8\";\r\n\ttitle=\"Grüße\"; a*0=\"b\"; a*1=c"
+            ).unwrap();
+            let normalized = mt.normalize_into::<HttpSpec<Modern>>().unwrap();
+            assert_eq!(
+                normalized.as_str_repr(),
+                "text/plain; charset=utf-8; title*=utf-8''Gr%C3%BC%C3%9Fe; a=bc"
+            );
+            assert!(MediaType::<HttpSpec<Modern>>::validate(normalized.as_str_repr()));
+        }
+
+        #[test]
+        fn requotes_minimally() {
+            let mt = MediaType::<HttpSpec<Obs>>::parse("text/plain; a=\"\\b\"; c=\"d e\"").unwrap();
+            let normalized = mt.normalize_into::<StrictSpec>().unwrap();
+            assert_eq!(normalized.as_str_repr(), "text/plain; a=b; c=\"d e\"");
+        }
+
+        #[test]
+        fn keeps_extended_values() {
+            let mt = MediaType::<HttpSpec<Modern>>::parse("text/plain; a*=iso-8859-1'de'%FC").unwrap();
+            let normalized = mt.normalize_into::<MimeSpec>().unwrap();
+            assert_eq!(normalized.as_str_repr(), "text/plain; a*=iso-8859-1'de'%FC");
+        }
+
+        #[test]
+        fn invalid_name_under_target() {
+            let mt = MediaType::<MimeSpec>::parse("text/plain; {a}=b").unwrap();
+            assert!(mt.normalize_into::<HttpSpec<Modern>>().is_err());
+        }
+
+        #[test]
+        fn invalid_extended_value_under_target() {
+            for input in &[
+                "text/plain; a*=\"utf-8''x%20y\"",
+                "text/plain; a*=utf.8''x",
+                "text/plain; a*=utf-8'en_US'x"
+            ] {
+                let mt = MediaType::<AnySpec>::parse(input).unwrap();
+                assert!(mt.normalize_into::<HttpSpec<Modern>>().is_err(), "{:?}", input);
+            }
+        }
+
+        fn assert_valid_if_ok<S2: Spec>(mt: &MediaType<AnySpec>) {
+            if let Ok(normalized) = mt.normalize_into::<S2>() {
+                assert!(
+                    MediaType::<S2>::validate(normalized.as_str_repr()),
+                    "{:?} normalized to {:?}", mt.as_str_repr(), normalized.as_str_repr()
+                );
+            }
+        }
+
+        #[test]
+        fn ok_results_are_valid_under_target() {
+            let long = "x".repeat(100);
+            let inputs = vec![
+                "text/plain; a*=\"utf-8''x%20y\"".to_owned(),
+                "text/plain; a*=utf.8''x; b*=utf-8'en_US'y".to_owned(),
+                "text/plain; a*=''x%20y; b*=utf-8'de'%FF".to_owned(),
+                "text/plain; a*0*=utf-8''x; a*1=\"y z\"; a*2*=%C3%A4".to_owned(),
+                "text/plain; a=\"\\\"\"; b=\"\"; c=\"Grüße\"".to_owned(),
+                "Text/Plain; (comment) a=\"b\r\n c\"".to_owned(),
+                "text/plain; {a}=b".to_owned(),
+                "application/vnd.a+json; x-y=z".to_owned(),
+                format!("text/plain; a=\"{} {}\"; b*=utf-8''{}", long, long, long),
+                format!("text/plain; {}=x", long),
+            ];
+            for input in &inputs {
+                let mt = match MediaType::<AnySpec>::parse(input) {
+                    Ok(mt) => mt,
+                    Err(_) => continue
+                };
+                assert_valid_if_ok::<StrictSpec>(&mt);
+                assert_valid_if_ok::<RegistrationSpec>(&mt);
+                assert_valid_if_ok::<HttpSpec<Modern>>(&mt);
+                assert_valid_if_ok::<HttpSpec<Obs>>(&mt);
+                assert_valid_if_ok::<MimeSpec<Ascii, Modern>>(&mt);
+                assert_valid_if_ok::<MimeSpec<Ascii, Obs, NoSplit>>(&mt);
+                assert_valid_if_ok::<MimeSpec>(&mt);
+                assert_valid_if_ok::<AnySpec>(&mt);
+            }
+        }
+    }
+
+    mod parse_header_line {
+        use super::super::MediaType;
+        use error::ErrorKind;
+        use parse::{HttpSpec, MimeSpec, Modern, Obs};
+
+        #[test]
+        fn http_obs_fold() {
+            let line = "content-type: Text/Plain;\r\n charset=utf-8\r\n";
+            let (mt, value_start) = MediaType::<HttpSpec<Obs>>::parse_header_line(line).unwrap();
+            assert_eq!(value_start, 14);
+            assert_eq!(mt.full_type(), "text/plain");
+            assert_eq!(mt.get_param("charset").unwrap(), "utf-8");
+        }
+
+        #[test]
+        fn mime_fws() {
+            let line = "Content-Type: multipart/mixed;\r\n\tboundary=\"a:b\";\r\n c=d\r\n";
+            let (mt, value_start) = MediaType::<MimeSpec>::parse_header_line(line).unwrap();
+            assert_eq!(value_start, 14);
+            assert_eq!(mt.get_param("boundary").unwrap().to_content(), "a:b");
+            assert_eq!(mt.get_param("c").unwrap(), "d");
+        }
+
+        #[test]
+        fn folding_not_allowed() {
+            let line = "Content-Type: text/plain;\r\n charset=utf-8\r\n";
+            let err = MediaType::<HttpSpec<Modern>>::parse_header_line(line).unwrap_err();
+            assert_eq!(err.input(), "Content-Type: text/plain;\r\n charset=utf-8");
+            assert_eq!(err.kind().pos(), 25);
+        }
+
+        #[test]
+        fn wrong_header_name() {
+            let err = MediaType::<HttpSpec<Obs>>::parse_header_line("Accept: text/plain").unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidHeaderName { pos: 0 });
+        }
+    }
+
+    mod whatwg {
+        use super::super::{AnyMediaType, MediaType};
+        use parse::{AnySpec, HttpSpec, Modern};
+
+        #[test]
+        fn parse_lenient() {
+            let mt = assert_ok!(AnyMediaType::parse_whatwg(
+                " Text/HTML ; charset=\"gbk\";; x ; charset=utf-8; a=\"b c\";"
+            ));
+            assert_eq!(mt.as_str_repr(), "text/html;charset=gbk;a=\"b c\"");
+            assert_eq!(mt.type_(), "text");
+            assert_eq!(mt.subtype(), "html");
+            assert_eq!(mt.get_param("charset").unwrap(), "gbk");
+            assert_eq!(mt.get_param("a").unwrap().to_content(), "b c");
+            assert_eq!(mt.params().len(), 2);
+        }
+
+        #[test]
+        fn same_as_strict_parsing() {
+            let whatwg = assert_ok!(AnyMediaType::parse_whatwg("application/vnd.a+json;x=\"y\""));
+            let http = assert_ok!(MediaType::<HttpSpec<Modern>>::parse("application/vnd.a+json; x=y"));
+            assert_eq!(whatwg, *http);
+            assert_eq!(whatwg.suffix(), http.suffix());
+        }
+
+        #[test]
+        fn invalid_type() {
+            assert!(AnyMediaType::parse_whatwg("text").is_err());
+            assert!(AnyMediaType::parse_whatwg("te xt/html").is_err());
+        }
+
+        #[test]
+        fn serialize() {
+            let mt = assert_ok!(MediaType::<AnySpec>::parse(
+                "text/plain; charset=utf-8; a=\"b\\\"c\"; e=\"\"; f=\"g\""
+            ));
+            assert_eq!(
+                mt.to_whatwg_string(),
+                "text/plain;charset=utf-8;a=\"b\\\"c\";e=\"\";f=g"
+            );
+        }
+    }
+
+    mod suffix {
+        use super::super::MediaType;
+        use name::{JSON, XML};
+        use parse::{AnySpec, HttpSpec, Modern};
+        use tree::RegistrationTree;
+
+        #[test]
+        fn parse_with_suffix() {
+            let mt = assert_ok!(MediaType::<AnySpec>::parse("application/vnd.foo+JSON; a=b"));
+            assert_eq!(mt.subtype(), "vnd.foo+json");
+            assert_eq!(mt.subtype_without_suffix(), "vnd.foo");
+            assert_eq!(mt.suffix(), Some(JSON));
+        }
+
+        #[test]
+        fn parse_without_suffix() {
+            let mt = assert_ok!(MediaType::<AnySpec>::parse("text/plain"));
+            assert_eq!(mt.subtype_without_suffix(), "plain");
+            assert_eq!(mt.suffix(), None);
+        }
+
+        #[test]
+        fn last_plus_is_used() {
+            let mt = assert_ok!(MediaType::<AnySpec>::parse("application/a+b+xml"));
+            assert_eq!(mt.subtype_without_suffix(), "a+b");
+            assert_eq!(mt.suffix(), Some(XML));
+        }
+
+        #[test]
+        fn new_with_suffix() {
+            let mt = assert_ok!(MediaType::<HttpSpec<Modern>>::new("image", "svg+xml"));
+            assert_eq!(mt.subtype_without_suffix(), "svg");
+            assert_eq!(mt.suffix(), Some(XML));
+        }
+
+        #[test]
+        fn tree_ignores_suffix() {
+            let mt = assert_ok!(MediaType::<AnySpec>::parse("application/vnd.foo.bar+json"));
+            assert_eq!(mt.tree(), RegistrationTree::Vendor("foo"));
+            let mt = assert_ok!(MediaType::<AnySpec>::parse("application/x-foo+json"));
+            assert_eq!(mt.tree(), RegistrationTree::LegacyX);
+        }
+
+        #[test]
+        fn suffix_is_part_of_equality() {
+            let json = assert_ok!(MediaType::<AnySpec>::parse("application/vnd.foo+json"));
+            let xml = assert_ok!(MediaType::<AnySpec>::parse("application/vnd.foo+xml"));
+            let none = assert_ok!(MediaType::<AnySpec>::parse("application/vnd.foo"));
+            assert_ne!(json, xml);
+            assert_ne!(json, none);
+            assert_eq!(json, assert_ok!(MediaType::<AnySpec>::parse("Application/Vnd.Foo+Json")));
+        }
+    }
+
+    mod canonical {
+        use super::super::{AnyMediaType, MediaType};
+        use parse::{AnySpec, HttpSpec, MimeSpec, Obs};
+
+        fn canonical(input: &str) -> String {
+            let mt = assert_ok!(MediaType::<AnySpec>::parse(input));
+            mt.canonical().as_str_repr().to_owned()
+        }
+
+        #[test]
+        fn sorts_and_unquotes_params() {
+            assert_eq!(
+                canonical("Text/Plain; b=\"x\"; Charset=\"UTF8\"; a=y"),
+                "text/plain; a=y; b=x; charset=utf-8"
+            );
+            assert_eq!(canonical("text/plain; a=\"b c\"; a=\"\\b\""), "text/plain; a=b; a=\"b c\"");
+        }
+
+        #[test]
+        fn removes_comments_and_whitespace() {
+            assert_eq!(
+                canonical("multipart/mixed; (comment)\r\n boundary=\"abc\"  "),
+                "multipart/mixed; boundary=abc"
+            );
+        }
+
+        #[test]
+        fn canonical_is_stable() {
+            let mt = assert_ok!(MediaType::<AnySpec>::parse("text/plain; B=x; charset=UTF-8"));
+            let canonical = mt.canonical();
+            assert_eq!(canonical.canonical().as_str_repr(), canonical.as_str_repr());
+            assert_eq!(canonical.params().len(), 2);
+            assert_eq!(canonical.get_param("b").unwrap(), "x");
+        }
+
+        #[test]
+        fn eq_iff_canonical_eq() {
+            let inputs = [
+                "text/plain; charset=utf-8",
+                "text/plain; charset=UTF8",
+                "TEXT/plain; Charset=\"utf-8\"",
+                "text/plain; charset=latin1",
+                "text/plain; a=b; charset=utf-8",
+                "text/plain; charset=utf-8; a=b",
+                "text/plain; a=B; charset=utf-8",
+                "text/plain; a=\"b\"; charset=utf-8",
+                "text/html",
+                "text/html; a=b; a=c",
+                "text/html; a=c; a=b",
+                "text/html; a=b; a=b"
+            ];
+            let media_types = inputs.iter()
+                .map(|input| assert_ok!(MediaType::<HttpSpec<Obs>>::parse(input)))
+                .collect::<Vec<_>>();
+            for left in media_types.iter() {
+                for right in media_types.iter() {
+                    let canonical_eq = left.canonical().as_str_repr() == right.canonical().as_str_repr();
+                    assert_eq!(left == right, canonical_eq, "{} == {}", left, right);
+                }
+            }
+            assert_eq!(media_types[0], media_types[2]);
+            assert_ne!(media_types[0], media_types[3]);
+            assert_eq!(media_types[4], media_types[5]);
+            assert_ne!(media_types[4], media_types[6]);
+            assert_ne!(media_types[9], media_types[11]);
+        }
+
+        #[test]
+        fn generated_media_types() {
+            let generated = assert_ok!(MediaType::<MimeSpec>::new_with_params("Text", "Plain", vec![("A", "b")]));
+            let parsed = assert_ok!(MediaType::<MimeSpec>::parse("text/plain; a=b"));
+            assert_eq!(generated, parsed);
+            let canonical: AnyMediaType = generated.canonical();
+            assert_eq!(canonical.as_str_repr(), "text/plain; a=b");
+        }
+    }
+
+    mod hash {
+        use std::collections::hash_map::DefaultHasher;
+        use std::collections::HashMap;
+        use std::hash::{Hash, Hasher};
+        use super::super::{AnyMediaType, MediaType};
+        use parse::{AnySpec, HttpSpec, Obs};
+
+        fn hash_of<H: Hash>(value: &H) -> u64 {
+            let mut hasher = DefaultHasher::new();
+            value.hash(&mut hasher);
+            hasher.finish()
+        }
+
+        fn any(input: &str) -> AnyMediaType {
+            assert_ok!(MediaType::<AnySpec>::parse(input)).into()
+        }
+
+        #[test]
+        fn equal_media_types_have_equal_hashes() {
+            let pairs = [
+                ("text/plain", "TEXT/Plain"),
+                ("text/plain; a=b; c=d", "text/plain; c=\"d\"; A=b"),
+                ("text/plain; charset=UTF8", "text/plain;  charset=\"utf-8\""),
+                ("text/plain; (comment) a=b", "text/plain; a=b"),
+            ];
+            for &(left, right) in pairs.iter() {
+                let (left, right) = (any(left), any(right));
+                assert_eq!(left, right);
+                assert_eq!(hash_of(&left), hash_of(&right));
+            }
+            assert_ne!(hash_of(&any("text/plain; a=b")), hash_of(&any("text/plain; a=c")));
+            assert_eq!(hash_of(&any("text/plain; a=b; a=c")), hash_of(&any("text/plain; a=c; A=b")));
+            assert_eq!(
+                hash_of(&any("text/plain; a=b; c=d; e=f")),
+                hash_of(&any("text/plain; e=f; a=b; c=d"))
+            );
+        }
+
+        #[test]
+        fn usable_as_map_key() {
+            let mut map = HashMap::new();
+            map.insert(assert_ok!(MediaType::<HttpSpec<Obs>>::parse("text/plain; charset=utf-8; a=b")), 1);
+            let key = assert_ok!(MediaType::<HttpSpec<Obs>>::parse("Text/Plain; a=\"b\"; charset=UTF-8"));
+            assert_eq!(map.get(&key), Some(&1));
+            let mut any_map = HashMap::new();
+            any_map.insert(any("image/png"), 2);
+            assert_eq!(any_map.get(&any("Image/PNG")), Some(&2));
+        }
+    }
+
+    mod case_insensitive_params {
+        use super::super::{AnyMediaType, MediaType};
+        use parse::AnySpec;
+
+        fn any(input: &str) -> AnyMediaType {
+            assert_ok!(MediaType::<AnySpec>::parse(input)).into()
+        }
+
+        #[test]
+        fn registered_params_ignore_case() {
+            assert_eq!(any("text/plain; charset=UTF-8"), any("text/plain; charset=utf-8"));
+            assert_ne!(any("text/plain; format=Flowed; delsp=Yes"), any("text/plain; format=flowed; delsp=yes"));
+            assert_ne!(any("text/plain; boundary=ABC"), any("text/plain; boundary=abc"));
+        }
+
+        #[test]
+        fn additional_params_ignore_case() {
+            let left = any("multipart/mixed; Boundary=ABC; charset=UTF-8");
+            let right = any("multipart/mixed; boundary=\"abc\"; charset=utf8");
+            assert!(left.eq_with_case_insensitive(&right, &["BOUNDARY"]));
+            assert!(!left.eq_with_case_insensitive(&right, &["other"]));
+            assert!(!left.eq_with_case_insensitive(&any("multipart/mixed; boundary=abd; charset=utf8"), &["boundary"]));
+            let flowed = any("text/plain; format=Flowed; delsp=Yes");
+            assert!(flowed.eq_with_case_insensitive(&any("text/plain; format=flowed; delsp=yes"), &["format", "delsp"]));
+        }
+
+        #[test]
+        fn has_utf8_charset_ignores_case() {
+            assert!(any("text/plain; charset=UTF-8").has_utf8_charset());
+            assert!(any("text/plain; charset=\"Utf8\"").has_utf8_charset());
+            assert!(!any("text/plain; charset=latin1").has_utf8_charset());
+        }
+    }
+
+    mod ord {
+        use std::cmp::Ordering;
+        use std::collections::BTreeMap;
+        use super::super::MediaType;
+        use parse::{AnySpec, HttpSpec, Obs};
+
+        fn mt(input: &str) -> MediaType<HttpSpec<Obs>> {
+            assert_ok!(MediaType::parse(input))
+        }
+
+        #[test]
+        fn orders_by_type_subtype_and_params() {
+            let mut media_types = [
+                mt("text/plain; charset=utf-8"),
+                mt("image/png"),
+                mt("Text/HTML"),
+                mt("text/plain"),
+                mt("text/plain; a=b"),
+                mt("text/plain; a=\"a\""),
+            ];
+            media_types.sort();
+            let sorted = media_types.iter()
+                .map(|media_type| media_type.as_str_repr())
+                .collect::<Vec<_>>();
+            assert_eq!(sorted, vec![
+                "image/png",
+                "text/html",
+                "text/plain",
+                "text/plain; a=\"a\"",
+                "text/plain; a=b",
+                "text/plain; charset=utf-8"
+            ]);
+        }
+
+        #[test]
+        fn consistent_with_eq() {
+            let left = mt("text/plain; charset=UTF8; a=\"b\"");
+            let right = mt("TEXT/plain; a=b; charset=utf-8");
+            assert_eq!(left, right);
+            assert_eq!(left.cmp(&right), Ordering::Equal);
+            let other = assert_ok!(MediaType::<AnySpec>::parse("text/plain; a=c"));
+            assert_eq!(left.partial_cmp(&other), Some(Ordering::Less));
+        }
+
+        #[test]
+        fn usable_as_btree_map_key() {
+            let mut map = BTreeMap::new();
+            map.insert(mt("application/json"), 1);
+            map.insert(mt("text/plain; charset=utf-8"), 2);
+            assert_eq!(map.get(&mt("Application/JSON")), Some(&1));
+            assert_eq!(map.get(&mt("text/plain; charset=\"utf8\"")), Some(&2));
+            assert_eq!(map.get(&mt("text/plain")), None);
+        }
+
+        #[test]
+        fn few_params_ordered_like_canonical_params() {
+            let media_types = [
+                mt("text/plain"),
+                mt("text/plain; b=c"),
+                mt("text/plain; a=z"),
+                mt("text/plain; b=c; a=b"),
+                mt("text/plain; a=b; A=a"),
+                mt("text/plain; a=b; b=c; c=d"),
+                mt("text/plain; b=a; a=b; a=c"),
+            ];
+            for left in media_types.iter() {
+                for right in media_types.iter() {
+                    assert_eq!(
+                        left.cmp(right),
+                        left.canonical_params().cmp(&right.canonical_params()),
+                        "{} cmp {}", left, right
+                    );
+                }
+            }
+        }
+    }
+
     mod new {
         use super::super::MediaType;
         use error::{Error, ErrorKind, ExpectedChar};
@@ -757,6 +1909,319 @@ mod test {
         }
     }
 
+    mod set_extended_param {
+        use super::super::MediaType;
+        use decode::DecodedValue;
+        use extended_value::ExtendedValue;
+        use spec::{AnySpec, HttpSpec, MimeSpec, StrictSpec};
+
+        #[test]
+        fn add_to_empty() {
+            let mut mt = MediaType::<HttpSpec>::new("text","plain").unwrap();
+            assert_ok!(mt.set_extended_param("title", &ExtendedValue::utf8("a b")));
+            assert_eq!(mt.as_str_repr(), "text/plain; title*=utf-8''a%20b")
+        }
+
+        #[test]
+        fn attribute_chars_are_encoded() {
+            let mut mt = MediaType::<MimeSpec>::new("text","plain").unwrap();
+            assert_ok!(mt.set_extended_param("title", &ExtendedValue::utf8("100%'*")));
+            assert_eq!(mt.as_str_repr(), "text/plain; title*=utf-8''100%25%27%2A")
+        }
+
+        #[test]
+        fn replaces_plain_and_extended_param() {
+            let mut mt = MediaType::<HttpSpec>::new_with_params("text","plain", vec![
+                ("title", "a"),
+                ("foo", "bar")
+            ]).unwrap();
+            assert_ok!(mt.set_extended_param("title", &ExtendedValue::utf8("b")));
+            assert_ok!(mt.set_extended_param("title", &ExtendedValue::utf8("c")));
+            assert_eq!(mt.as_str_repr(), "text/plain; foo=bar; title*=utf-8''c")
+        }
+
+        #[test]
+        fn round_trip_latin1() {
+            let mt = MediaType::<AnySpec>::parse(
+                "text/plain; title*=iso-8859-1'de'Gr%FC%DFe").unwrap();
+            let value = match mt.get_decoded_param("title").unwrap() {
+                Some(DecodedValue::Extended(value)) => value,
+                other => panic!("unexpected value: {:?}", other)
+            };
+            assert_eq!(value.charset(), "iso-8859-1");
+            assert_eq!(value.language(), Some("de"));
+            assert_eq!(value.bytes(), b"Gr\xfc\xdfe");
+
+            let mut out = MediaType::<HttpSpec>::new("text","plain").unwrap();
+            assert_ok!(out.set_extended_param("title", &value));
+            assert_eq!(out.as_str_repr(), "text/plain; title*=iso-8859-1'de'Gr%FC%DFe");
+        }
+
+        #[test]
+        fn invalid_charset_or_language_is_an_error() {
+            let mt = MediaType::<AnySpec>::parse(
+                "text/plain; a*=utf.8''x; b*=utf-8'en_US'y").unwrap();
+            let mut out = MediaType::<HttpSpec>::new("text","plain").unwrap();
+            for name in &["a", "b"] {
+                let value = match mt.get_decoded_param(*name).unwrap() {
+                    Some(DecodedValue::Extended(value)) => value,
+                    other => panic!("unexpected value: {:?}", other)
+                };
+                assert!(out.set_extended_param(*name, &value).is_err());
+            }
+            assert_eq!(out.as_str_repr(), "text/plain");
+        }
+
+        #[test]
+        fn spec_without_extended_params() {
+            let mut mt = MediaType::<StrictSpec>::new_with_params("text","plain", vec![
+                ("title", "a")
+            ]).unwrap();
+            assert!(mt.set_extended_param("title", &ExtendedValue::utf8("b")).is_err());
+            assert_eq!(mt.as_str_repr(), "text/plain; title=a");
+        }
+
+        #[test]
+        fn unspecified_charset() {
+            let mt = MediaType::<AnySpec>::parse("text/plain; a*=''x%20y").unwrap();
+            let value = match mt.get_decoded_param("a").unwrap() {
+                Some(DecodedValue::Extended(value)) => value,
+                other => panic!("unexpected value: {:?}", other)
+            };
+            let mut out = MediaType::<HttpSpec>::new("text","plain").unwrap();
+            assert_ok!(out.set_extended_param("a", &value));
+            assert_eq!(out.as_str_repr(), "text/plain; a*=''x%20y");
+        }
+    }
+
+    mod split_params {
+        use super::super::MediaType;
+        use decode::DecodedValue;
+        use spec::{
+            MimeSpec, HttpSpec, StrictSpec, AnySpec, Ascii, Internationalized,
+            Modern, Obs, NoSplit, SplitThreshold
+        };
+
+        #[derive(Copy, Clone, Debug, Default)]
+        struct Max20;
+        impl SplitThreshold for Max20 {
+            fn max_param_len() -> Option<usize> {
+                Some(20)
+            }
+        }
+
+        type SmallMime = MimeSpec<Ascii, Modern, Max20>;
+
+        fn decoded_str(mt: &MediaType<SmallMime>, name: &str) -> String {
+            match mt.get_decoded_param(name).unwrap().unwrap() {
+                DecodedValue::Plain(value) => value.into_owned(),
+                DecodedValue::Extended(value) => value.to_str().unwrap().into_owned()
+            }
+        }
+
+        #[test]
+        fn short_params_are_not_split() {
+            let mt = MediaType::<SmallMime>::new_with_params("text", "plain", vec![
+                ("name", "short")
+            ]).unwrap();
+            assert_eq!(mt.as_str_repr(), "text/plain; name=short");
+        }
+
+        #[test]
+        fn split_plain() {
+            let mt = MediaType::<SmallMime>::new_with_params("text", "plain", vec![
+                ("name", "abcdefghijklmnopqrstuvwxyz")
+            ]).unwrap();
+            assert_eq!(mt.as_str_repr(), "text/plain; name*0=abcdefghijklm; name*1=nopqrstuvwxyz");
+            assert_eq!(mt.params().len(), 2);
+            assert_eq!(decoded_str(&mt, "name"), "abcdefghijklmnopqrstuvwxyz");
+        }
+
+        #[test]
+        fn split_quoted() {
+            let mt = MediaType::<SmallMime>::new_with_params("text", "plain", vec![
+                ("n", "a b c d e f g h i j")
+            ]).unwrap();
+            assert_eq!(mt.as_str_repr(), r#"text/plain; n*0="a b c d e f g "; n*1="h i j""#);
+            assert_eq!(decoded_str(&mt, "n"), "a b c d e f g h i j");
+        }
+
+        #[test]
+        fn split_encoded() {
+            let mt = MediaType::<SmallMime>::new_with_params("text", "plain", vec![
+                ("n", "ääääää")
+            ]).unwrap();
+            assert_eq!(
+                mt.as_str_repr(),
+                "text/plain; n*0*=utf-8''%C3%A4; n*1*=%C3%A4%C3%A4%C3; n*2*=%A4%C3%A4%C3%A4"
+            );
+            assert_eq!(decoded_str(&mt, "n"), "ääääää");
+        }
+
+        #[test]
+        fn indices_are_correct() {
+            let mt = MediaType::<SmallMime>::new_with_params("text", "plain", vec![
+                ("name", "abcdefghijklmnopqrstuvwxyz"),
+                ("other", "value")
+            ]).unwrap();
+            let params = mt.params().collect::<Vec<_>>();
+            assert_eq!(params.len(), 3);
+            assert_eq!(params[0].0, "name*0");
+            assert_eq!(params[0].1, "abcdefghijklm");
+            assert_eq!(params[1].0, "name*1");
+            assert_eq!(params[1].1, "nopqrstuvwxyz");
+            assert_eq!(params[2].0, "other");
+            assert_eq!(params[2].1, "value");
+        }
+
+        #[test]
+        fn default_mime_spec_splits_at_78() {
+            let long_name = "a".repeat(100);
+            let mt = MediaType::<MimeSpec>::new_with_params("application", "pdf", vec![
+                ("filename", long_name.as_str())
+            ]).unwrap();
+            assert_eq!(mt.params().len(), 2);
+            for (name, value) in mt.params() {
+                assert!(name.len() + 1 + value.as_str_repr().len() <= 76);
+            }
+        }
+
+        #[test]
+        fn split_escaped() {
+            let mt = MediaType::<SmallMime>::new_with_params("text", "plain", vec![
+                ("n", r#"a"b"c"d"e"f"g"h"#)
+            ]).unwrap();
+            assert_eq!(mt.as_str_repr(), r#"text/plain; n*0="a\"b\"c\"d\"e"; n*1="\"f\"g\"h""#);
+            for (name, value) in mt.params() {
+                assert!(name.len() + 1 + value.as_str_repr().len() <= 20);
+            }
+            assert_eq!(decoded_str(&mt, "n"), r#"a"b"c"d"e"f"g"h"#);
+        }
+
+        #[test]
+        fn empty_value_with_long_name_is_not_dropped() {
+            let long_name = "a".repeat(90);
+            let mt = MediaType::<MimeSpec>::new_with_params("text", "plain", vec![
+                (long_name.as_str(), "")
+            ]).unwrap();
+            assert_eq!(mt.as_str_repr(), format!("text/plain; {}=\"\"", long_name));
+            assert_eq!(mt.params().len(), 1);
+            let value = mt.get_param(long_name.as_str()).unwrap();
+            assert_eq!(value.to_content(), "");
+        }
+
+        #[test]
+        fn name_leaving_no_room_is_not_split() {
+            let long_name = "a".repeat(18);
+            let mt = MediaType::<SmallMime>::new_with_params("text", "plain", vec![
+                (long_name.as_str(), "value")
+            ]).unwrap();
+            assert_eq!(mt.as_str_repr(), format!("text/plain; {}=value", long_name));
+            assert_eq!(mt.params().len(), 1);
+
+            let mt = MediaType::<SmallMime>::new_with_params("text", "plain", vec![
+                ("n", "ääääää")
+            ]).unwrap();
+            let mut out = MediaType::<SmallMime>::new("text", "plain").unwrap();
+            let value = match mt.get_decoded_param("n").unwrap().unwrap() {
+                DecodedValue::Extended(value) => value,
+                other => panic!("unexpected value: {:?}", other)
+            };
+            out.set_extended_param(long_name.as_str(), &value).unwrap();
+            assert_eq!(
+                out.as_str_repr(),
+                format!("text/plain; {}*=utf-8''%C3%A4%C3%A4%C3%A4%C3%A4%C3%A4%C3%A4", long_name)
+            );
+        }
+
+        #[test]
+        fn no_split() {
+            let long_name = "a".repeat(100);
+            let mt = MediaType::<MimeSpec<Ascii, Obs, NoSplit>>::new_with_params(
+                "application", "pdf", vec![("filename", long_name.as_str())]
+            ).unwrap();
+            assert_eq!(mt.params().len(), 1);
+        }
+
+        #[test]
+        fn conversions_cover_all_thresholds() {
+            let mt = MediaType::<MimeSpec<Ascii, Modern, NoSplit>>::new("text", "plain").unwrap();
+            let mt: MediaType<MimeSpec<Ascii, Obs>> = mt.into();
+            let mt: MediaType<MimeSpec<Internationalized, Obs, NoSplit>> = mt.into();
+            let mt: MediaType<AnySpec> = mt.into();
+            assert_eq!(mt.as_str_repr(), "text/plain");
+            let mt: MediaType<MimeSpec<Internationalized, Modern, NoSplit>> =
+                MediaType::<StrictSpec>::new("text", "plain").unwrap().into();
+            assert_eq!(mt.as_str_repr(), "text/plain");
+        }
+
+        #[test]
+        fn http_does_not_split() {
+            let long_name = "a".repeat(100);
+            let mt = MediaType::<HttpSpec>::new_with_params(
+                "application", "pdf", vec![("filename", long_name.as_str())]
+            ).unwrap();
+            assert_eq!(mt.params().len(), 1);
+        }
+
+        #[test]
+        fn set_param_replaces_all_sections() {
+            let mut mt = MediaType::<SmallMime>::new_with_params("text", "plain", vec![
+                ("name", "abcdefghijklmnopqrstuvwxyz"),
+                ("other", "value")
+            ]).unwrap();
+            mt.set_param("name", "short");
+            assert_eq!(mt.as_str_repr(), "text/plain; other=value; name=short");
+            mt.set_param("name", "ääääää");
+            mt.set_param("name", "abcdefghijklmnopqrstuvwxyz");
+            assert_eq!(
+                mt.as_str_repr(),
+                "text/plain; other=value; name*0=abcdefghijklm; name*1=nopqrstuvwxyz"
+            );
+        }
+    }
+
+    mod write_folded {
+        use super::super::MediaType;
+        use spec::{AnySpec, MimeSpec, Ascii, Modern};
+
+        #[test]
+        fn folds_at_parameter_boundaries() {
+            let mt = MediaType::<MimeSpec>::new_with_params("multipart", "mixed", vec![
+                ("boundary", "=_0123456789abcdef0123456789abcdef0123456789"),
+                ("charset", "utf-8"),
+                ("foo", "bar")
+            ]).unwrap();
+            let folded = mt.to_folded_string("Content-Type: ".len(), 78);
+            assert_eq!(folded, concat!(
+                "multipart/mixed;\r\n",
+                " boundary=\"=_0123456789abcdef0123456789abcdef0123456789\"; charset=utf-8;\r\n",
+                " foo=bar"
+            ));
+        }
+
+        #[test]
+        fn output_parses_with_same_spec() {
+            let mt = MediaType::<MimeSpec<Ascii, Modern>>::parse(
+                "text/plain; charset=utf-8; format=flowed; delsp=yes; x=\"y z\""
+            ).unwrap();
+            let folded = mt.to_folded_string(0, 20);
+            for line in folded.split("\r\n") {
+                assert!(line.len() <= 20, "line to long: {:?}", line);
+            }
+            let reparsed = MediaType::<MimeSpec<Ascii, Modern>>::parse(&folded).unwrap();
+            assert_eq!(reparsed, mt);
+        }
+
+        #[test]
+        fn works_for_any_spec() {
+            let mt = MediaType::<AnySpec>::parse("text/plain; charset=utf-8").unwrap();
+            let folded = mt.to_folded_string(20, 30);
+            assert_eq!(folded, "text/plain;\r\n charset=utf-8");
+            assert_eq!(MediaType::<AnySpec>::parse(&folded).unwrap(), mt);
+        }
+    }
+
     #[test]
     fn media_type_conversion_mime() {
         let top = MediaType::<StrictSpec>::parse("text/plain").unwrap();
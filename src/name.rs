@@ -35,12 +35,30 @@ def_static_names! {
     //TODO more of them
 }
 
+// the structured syntax suffixes (RFC 6839, RFC 7303)
+def_static_names! {
+    JSON = "json";
+    XML = "xml";
+    BER = "ber";
+    DER = "der";
+    FASTINFOSET = "fastinfoset";
+    WBXML = "wbxml";
+    ZIP = "zip";
+}
+
 // some fields
 def_static_names! {
     CHARSET = "charset";
     BOUNDARY = "boundary";
 }
 
+/// parameters whose values are compared case-insensitive when comparing media types
+///
+/// Only `charset` is case-insensitive for all media types (RFC 2046). Parameters
+/// which are only case-insensitive for some media types (e.g. `format` and `delsp`
+/// of `text/plain`, RFC 3676) can be compared with `eq_with_case_insensitive`.
+pub const CASE_INSENSITIVE_PARAMS: &[Name] = &[CHARSET];
+
 
 
 //TODO add Spec :=/
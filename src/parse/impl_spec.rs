@@ -1,11 +1,25 @@
 
+use std::borrow::Cow;
+
 use quoted_string::spec::GeneralQSSpec;
 use media_type_impl_utils::quoted_string::{self as impl_qs_spec, MimeParsingExt};
 use media_type_impl_utils::percent_encoding::{MimePercentEncodeSet, HttpPercentEncodeSet};
 
-use error::ParserErrorRef;
+use lut::Table;
+use media_type_impl_utils::lookup_tables::{MediaTypeChars, HttpToken, RestrictedToken};
+
+use error::{ParserErrorRef, ErrorKind};
 use spec::*;
 
+/// the max length of a RFC 6838 restricted-name
+const MAX_RESTRICTED_NAME_LEN: usize = 127;
+
+/// the top-level types registered at IANA
+const KNOWN_TOP_LEVEL_TYPES: &[&str] = &[
+    "application", "audio", "example", "font", "haptics", "image",
+    "message", "model", "multipart", "text", "video"
+];
+
 impl GeneralQSSpec for AnySpec {
     type Quoting = impl_qs_spec::AnyQuoting;
     type Parsing = impl_qs_spec::AnyParsingImpl;
@@ -16,6 +30,11 @@ impl GeneralQSSpec for StrictSpec {
     type Parsing = impl_qs_spec::StrictParsingImpl;
 }
 
+impl GeneralQSSpec for RegistrationSpec {
+    type Quoting = impl_qs_spec::NormalQuoting;
+    type Parsing = impl_qs_spec::StrictParsingImpl;
+}
+
 impl GeneralQSSpec for HttpSpec<Modern> {
     type Quoting = impl_qs_spec::NormalQuoting;
     type Parsing = impl_qs_spec::NormalParsingImpl;
@@ -26,22 +45,22 @@ impl GeneralQSSpec for HttpSpec<Obs> {
     type Parsing = impl_qs_spec::HttpObsParsingImpl;
 }
 
-impl GeneralQSSpec for MimeSpec<Ascii, Modern> {
+impl<ST: SplitThreshold> GeneralQSSpec for MimeSpec<Ascii, Modern, ST> {
     type Quoting = impl_qs_spec::NormalQuoting;
     type Parsing = impl_qs_spec::MimeParsing;
 }
 
-impl GeneralQSSpec for MimeSpec<Ascii, Obs> {
+impl<ST: SplitThreshold> GeneralQSSpec for MimeSpec<Ascii, Obs, ST> {
     type Quoting = impl_qs_spec::MimeObsQuoting;
     type Parsing = impl_qs_spec::MimeObsParsing;
 }
 
-impl GeneralQSSpec for MimeSpec<Internationalized, Modern> {
+impl<ST: SplitThreshold> GeneralQSSpec for MimeSpec<Internationalized, Modern, ST> {
     type Quoting = impl_qs_spec::NormalUtf8Quoting;
     type Parsing = impl_qs_spec::MimeParsingUtf8;
 }
 
-impl GeneralQSSpec for MimeSpec<Internationalized, Obs> {
+impl<ST: SplitThreshold> GeneralQSSpec for MimeSpec<Internationalized, Obs, ST> {
     type Quoting = impl_qs_spec::MimeObsUtf8Quoting;
     type Parsing = impl_qs_spec::MimeObsParsingUtf8;
 }
@@ -78,10 +97,72 @@ impl Spec for AnySpec {
         parse_opt_cfws::<<MimeSpec<Internationalized, Obs> as GeneralQSSpec>::Parsing>(input)
     }
 
+    fn allows_obs_text() -> bool {
+        true
+    }
+
+    fn unfold(input: &str) -> Cow<str> {
+        remove_folds(input)
+    }
+
     type UnquotedValue = impl_qs_spec::MimeTokenValidator;
 }
 
 
+impl Spec for RegistrationSpec {
+    type PercentEncodeSet = HttpPercentEncodeSet;
+
+    fn parse_token(input: &str) -> Result<usize, ParserErrorRef> {
+        StrictSpec::parse_token(input)
+    }
+
+    fn parse_space(input: &str) -> Result<usize, ParserErrorRef> {
+        Ok(parse_opt_ws(input))
+    }
+
+    fn parse_type(input: &str) -> Result<usize, ParserErrorRef> {
+        let end = parse_restricted_name(input)?;
+        let type_ = &input[..end];
+        if KNOWN_TOP_LEVEL_TYPES.iter().any(|known| known.eq_ignore_ascii_case(type_)) {
+            Ok(end)
+        } else {
+            Err(ErrorKind::UnknownTopLevelType { pos: 0 }.with_input(input))
+        }
+    }
+
+    fn parse_subtype(input: &str) -> Result<usize, ParserErrorRef> {
+        parse_restricted_name(input)
+    }
+
+    type UnquotedValue = impl_qs_spec::HttpTokenValidator;
+}
+
+/// parses a RFC 6838 restricted-name
+///
+/// The name ends at the first char which is not a http token char,
+/// if it contains token chars which are not valid in a restricted-name
+/// a `InvalidRestrictedChar` error is returned.
+fn parse_restricted_name(input: &str) -> Result<usize, ParserErrorRef> {
+    let bytes = input.as_bytes();
+    let end = bytes.iter()
+        .position(|&bch| !MediaTypeChars::check_at(bch as usize, HttpToken))
+        .unwrap_or(bytes.len());
+    let name = &bytes[..end];
+
+    if !name.first().map(|bch| bch.is_ascii_alphanumeric()).unwrap_or(false) {
+        return Err(ErrorKind::InvalidNameStart { pos: 0 }.with_input(input));
+    }
+    let invalid = name.iter()
+        .position(|&bch| !MediaTypeChars::check_at(bch as usize, RestrictedToken));
+    if let Some(pos) = invalid {
+        return Err(ErrorKind::InvalidRestrictedChar { pos }.with_input(input));
+    }
+    if end > MAX_RESTRICTED_NAME_LEN {
+        return Err(ErrorKind::NameTooLong { pos: MAX_RESTRICTED_NAME_LEN }.with_input(input));
+    }
+    Ok(end)
+}
+
 impl<O> Spec for HttpSpec<O>
     where O: ObsNormalSwitch, HttpSpec<O>: GeneralQSSpec
 {
@@ -95,15 +176,28 @@ impl<O> Spec for HttpSpec<O>
         Ok(parse_opt_ws(input))
     }
 
+    fn allows_obs_text() -> bool {
+        O::OBS
+    }
+
+    fn unfold(input: &str) -> Cow<str> {
+        if O::OBS {
+            replace_obs_folds(input)
+        } else {
+            Cow::Borrowed(input)
+        }
+    }
+
     type UnquotedValue = impl_qs_spec::HttpTokenValidator;
 
 }
 
-impl<I, O> Spec for MimeSpec<I, O>
+impl<I, O, ST> Spec for MimeSpec<I, O, ST>
     where O: ObsNormalSwitch,
           I: InternationalizedSwitch,
-          MimeSpec<I, O>: GeneralQSSpec,
-          <MimeSpec<I,O> as GeneralQSSpec>::Parsing: MimeParsingExt
+          ST: SplitThreshold,
+          MimeSpec<I, O, ST>: GeneralQSSpec,
+          <MimeSpec<I, O, ST> as GeneralQSSpec>::Parsing: MimeParsingExt
 {
 
     type PercentEncodeSet = MimePercentEncodeSet;
@@ -115,9 +209,59 @@ impl<I, O> Spec for MimeSpec<I, O>
 
     fn parse_space(input: &str) -> Result<usize, ParserErrorRef> {
         use super::parse_cfws::parse_opt_cfws;
-        parse_opt_cfws::<<MimeSpec<I,O> as GeneralQSSpec>::Parsing>(input)
+        parse_opt_cfws::<<MimeSpec<I, O, ST> as GeneralQSSpec>::Parsing>(input)
+    }
+
+    fn param_split_threshold() -> Option<usize> {
+        ST::max_param_len()
+    }
+
+    fn unfold(input: &str) -> Cow<str> {
+        remove_folds(input)
+    }
+
+}
+
+/// replaces each `"\r\n"` followed by white space with two spaces
+///
+/// As the length doesn't change positions in the unfolded input
+/// are the same as in the input.
+fn replace_obs_folds(input: &str) -> Cow<str> {
+    if find_fold(input, 0).is_none() {
+        return Cow::Borrowed(input);
+    }
+    let mut unfolded = String::with_capacity(input.len());
+    let mut offset = 0;
+    while let Some(fold_idx) = find_fold(input, offset) {
+        unfolded.push_str(&input[offset..fold_idx]);
+        unfolded.push_str("  ");
+        offset = fold_idx + 2;
+    }
+    unfolded.push_str(&input[offset..]);
+    Cow::Owned(unfolded)
+}
+
+/// removes each `"\r\n"` followed by white space
+fn remove_folds(input: &str) -> Cow<str> {
+    if find_fold(input, 0).is_none() {
+        return Cow::Borrowed(input);
     }
+    let mut unfolded = String::with_capacity(input.len());
+    let mut offset = 0;
+    while let Some(fold_idx) = find_fold(input, offset) {
+        unfolded.push_str(&input[offset..fold_idx]);
+        offset = fold_idx + 2;
+    }
+    unfolded.push_str(&input[offset..]);
+    Cow::Owned(unfolded)
+}
 
+/// returns the index of the next `"\r\n"` followed by a space or tab
+fn find_fold(input: &str, start: usize) -> Option<usize> {
+    let bytes = input.as_bytes();
+    input[start..].match_indices("\r\n")
+        .map(|(idx, _)| start + idx)
+        .find(|&idx| bytes.get(idx + 2).map(|&bch| bch == b' ' || bch == b'\t').unwrap_or(false))
 }
 
 fn parse_opt_ws(input: &str) -> usize {
@@ -125,3 +269,101 @@ fn parse_opt_ws(input: &str) -> usize {
         .position(|iu8| iu8 != b' ' && iu8 != b'\t')
         .unwrap_or(input.len())
 }
+
+#[cfg(test)]
+mod test {
+
+    mod unfold {
+        use std::borrow::Cow;
+        use spec::{HttpSpec, Obs, Modern, MimeSpec, StrictSpec, Spec};
+
+        #[test]
+        fn http_obs_replaces_folds() {
+            assert_eq!(
+                HttpSpec::<Obs>::unfold("text/plain;\r\n charset=utf-8"),
+                Cow::Owned::<str>("text/plain;   charset=utf-8".to_owned())
+            );
+            assert_eq!(HttpSpec::<Obs>::unfold("a\r\nb"), Cow::Borrowed("a\r\nb"));
+        }
+
+        #[test]
+        fn mime_removes_folds() {
+            assert_eq!(
+                <MimeSpec>::unfold("text/plain; a=\"b\r\n\tc\";\r\n d=e"),
+                Cow::Owned::<str>("text/plain; a=\"b\tc\"; d=e".to_owned())
+            );
+        }
+
+        #[test]
+        fn others_do_not_unfold() {
+            assert_eq!(HttpSpec::<Modern>::unfold("a;\r\n b"), Cow::Borrowed("a;\r\n b"));
+            assert_eq!(StrictSpec::unfold("a;\r\n b"), Cow::Borrowed("a;\r\n b"));
+        }
+    }
+
+    mod registration_spec {
+        use error::ErrorKind;
+        use media_type::MediaType;
+        use spec::{RegistrationSpec, StrictSpec, Spec};
+
+        fn kind_of(input: &str) -> ErrorKind {
+            MediaType::<RegistrationSpec>::parse(input).unwrap_err().kind()
+        }
+
+        #[test]
+        fn accepts_registrable_types() {
+            assert_ok!(MediaType::<RegistrationSpec>::parse("text/plain; charset=utf-8"));
+            assert_ok!(MediaType::<RegistrationSpec>::parse("application/vnd.ms-excel"));
+            assert_ok!(MediaType::<RegistrationSpec>::parse("Image/SVG+XML"));
+            assert_ok!(MediaType::<RegistrationSpec>::parse("haptics/ivs"));
+        }
+
+        #[test]
+        fn rejects_invalid_name_start() {
+            assert_eq!(kind_of("text/.plain"), ErrorKind::InvalidNameStart { pos: 5 });
+            assert_eq!(kind_of("text/"), ErrorKind::InvalidNameStart { pos: 5 });
+        }
+
+        #[test]
+        fn rejects_invalid_restricted_chars() {
+            assert_eq!(kind_of("text/pl*in"), ErrorKind::InvalidRestrictedChar { pos: 7 });
+            assert_eq!(kind_of("text/pl~in"), ErrorKind::InvalidRestrictedChar { pos: 7 });
+        }
+
+        #[test]
+        fn rejects_too_long_names() {
+            let subtype = "a".repeat(128);
+            assert_eq!(kind_of(&format!("text/{}", subtype)), ErrorKind::NameTooLong { pos: 132 });
+            assert_ok!(MediaType::<RegistrationSpec>::parse(&format!("text/{}", &subtype[1..])));
+        }
+
+        #[test]
+        fn rejects_unknown_top_level_types() {
+            assert_eq!(kind_of("foo/plain"), ErrorKind::UnknownTopLevelType { pos: 0 });
+            assert_ok!(MediaType::<StrictSpec>::parse("foo/plain"));
+        }
+
+        #[test]
+        fn new_validates_names() {
+            let err = MediaType::<RegistrationSpec>::new("bar", "plain").unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::UnknownTopLevelType { pos: 0 });
+            let err = MediaType::<RegistrationSpec>::new("text", "-plain").unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidNameStart { pos: 0 });
+            assert_ok!(MediaType::<RegistrationSpec>::new("text", "plain"));
+        }
+
+        #[test]
+        fn validate() {
+            assert!(MediaType::<RegistrationSpec>::validate("video/mp4"));
+            assert!(!MediaType::<RegistrationSpec>::validate("x-video/mp4"));
+            assert!(RegistrationSpec::validate_type("model").is_ok());
+        }
+
+        #[test]
+        fn converts_to_strict_spec() {
+            let mt = assert_ok!(MediaType::<RegistrationSpec>::parse("text/plain"));
+            let strict: MediaType<StrictSpec> = mt.into();
+            assert_eq!(strict.as_str_repr(), "text/plain");
+        }
+    }
+}
@@ -1,15 +1,19 @@
 #[allow(unused_imports, deprecated)]
 use std::ascii::AsciiExt;
 
-use error::ParserErrorRef;
+use error::{ParserErrorRef, ErrorKind, ExpectedChar, Component};
 use self::utils::parse_ascii_char;
+pub(crate) use self::utils::find_unquoted;
 
 pub use ::spec::{
     Spec,
     StrictSpec,
+    RegistrationSpec,
     MimeSpec, HttpSpec,
     Obs, Modern, Internationalized, Ascii,
     ObsNormalSwitch, InternationalizedSwitch,
+    SplitThreshold, SplitAt78, NoSplit,
+    FoldingSpec,
     AnySpec
 };
 
@@ -29,12 +33,32 @@ pub struct ParamIndices {
 pub(crate) struct ParseResult<'a> {
     pub(crate) input: &'a str,
     pub(crate) slash_idx: usize,
+    /// the index of the `+` before the suffix or `end_of_type_idx` if there is none
+    pub(crate) plus_idx: usize,
     pub(crate) end_of_type_idx: usize,
     pub(crate) params: Vec<ParamIndices>
 }
 
 impl<'a> ParseResult<'a> {
 
+    /// rebases all indices to be relative to `offset` and cuts of the input before it
+    pub(crate) fn rebase(self, offset: usize) -> ParseResult<'a> {
+        let ParseResult { input, slash_idx, plus_idx, end_of_type_idx, params } = self;
+        ParseResult {
+            input: &input[offset..],
+            slash_idx: slash_idx - offset,
+            plus_idx: plus_idx - offset,
+            end_of_type_idx: end_of_type_idx - offset,
+            params: params.into_iter()
+                .map(|indices| ParamIndices {
+                    start: indices.start - offset,
+                    eq_idx: indices.eq_idx - offset,
+                    end: indices.end - offset
+                })
+                .collect()
+        }
+    }
+
     pub(crate) fn repr_len(&self) -> usize {
         self.params
             .last()
@@ -49,59 +73,207 @@ pub(crate) fn validate<S: Spec>(input: &str) -> bool {
 
 pub(crate) fn parse<'a, S: Spec>(input: &'a str) -> Result<ParseResult, ParserErrorRef<'a>> {
     let (slash_idx, end_of_type_idx) = parse_media_type_head::<S>(input)?;
+    let plus_idx = find_plus_idx(input, slash_idx, end_of_type_idx);
     let params = parse_media_type_params::<S>(input, end_of_type_idx)?;
-    Ok(ParseResult { input, slash_idx, end_of_type_idx, params })
+    Ok(ParseResult { input, slash_idx, plus_idx, end_of_type_idx, params })
 }
 
+/// parses a media range (e.g. `text/*`) instead of a media type
+///
+/// The type and/or subtype can be the wildcard `*` (even if it is no
+/// token char in `S`), but if the type is a wildcard the subtype has
+/// to be one, too.
+pub(crate) fn parse_range<'a, S: Spec>(input: &'a str) -> Result<ParseResult, ParserErrorRef<'a>> {
+    parse_range_at::<S>(input, 0)
+}
+
+/// like `parse_range` but starts parsing at `offset`
+///
+/// All indices in the result are relative to `input`, not `offset`.
+pub(crate) fn parse_range_at<'a, S: Spec>(input: &'a str, offset: usize)
+    -> Result<ParseResult<'a>, ParserErrorRef<'a>>
+{
+    let (slash_idx, end_of_type_idx) = parse_media_range_head::<S>(input, offset)?;
+    let plus_idx = find_plus_idx(input, slash_idx, end_of_type_idx);
+    let params = parse_media_type_params::<S>(input, end_of_type_idx)?;
+    Ok(ParseResult { input, slash_idx, plus_idx, end_of_type_idx, params })
+}
 
+/// returns the index of the `+` separating the structured syntax suffix from the subtype
+///
+/// This is the last `+` in the subtype if it has chars on both sides, if
+/// there is no such `+` `end_of_type_idx` is returned.
+pub(crate) fn find_plus_idx(input: &str, slash_idx: usize, end_of_type_idx: usize) -> usize {
+    let subtype_start = slash_idx + 1;
+    match input[subtype_start..end_of_type_idx].rfind('+') {
+        Some(idx) if idx > 0 && subtype_start + idx + 1 < end_of_type_idx => subtype_start + idx,
+        _ => end_of_type_idx
+    }
+}
 
 fn parse_media_type_head<S: Spec>(input: &str) -> Result<(usize, usize), ParserErrorRef> {
-    let slash_idx = S::parse_token(input)?;
-    let start_of_subtype = parse_ascii_char(input, slash_idx, b'/')?;
-    let end_of_type_idx = at_pos!(start_of_subtype do S::parse_token | input);
+    let slash_idx = S::parse_type(input)
+        .map_err(|err| err.in_component(Component::Type))?;
+    let start_of_subtype = parse_ascii_char(input, slash_idx, b'/')
+        .map_err(|err| err.in_component(Component::Type))?;
+    let end_of_type_idx =
+        at_pos!(start_of_subtype do S::parse_subtype | input; Component::Subtype);
     Ok((slash_idx, end_of_type_idx))
 }
 
+fn parse_media_range_head<S: Spec>(input: &str, offset: usize)
+    -> Result<(usize, usize), ParserErrorRef>
+{
+    let slash_idx = parse_range_name(&input[offset..], S::parse_type)
+        .map_err(|err| err.rebased(input, offset).in_component(Component::Type))?
+        + offset;
+    let start_of_subtype = parse_ascii_char(input, slash_idx, b'/')
+        .map_err(|err| err.in_component(Component::Type))?;
+    let end_of_type_idx = parse_range_name(&input[start_of_subtype..], S::parse_subtype)
+        .map_err(|err| err.rebased(input, start_of_subtype).in_component(Component::Subtype))?
+        + start_of_subtype;
+    if &input[offset..slash_idx] == "*" && &input[start_of_subtype..end_of_type_idx] != "*" {
+        return Err(ErrorKind::UnexpectedChar {
+            pos: start_of_subtype, expected: ExpectedChar::Char('*')
+        }.with_input(input).in_component(Component::Subtype));
+    }
+    Ok((slash_idx, end_of_type_idx))
+}
 
+/// parses a (sub)type with `parse_name` or the wildcard `*`
+fn parse_range_name(input: &str, parse_name: fn(&str) -> Result<usize, ParserErrorRef>)
+    -> Result<usize, ParserErrorRef>
+{
+    match parse_name(input) {
+        Err(_) if input.starts_with('*') => Ok(1),
+        other => other
+    }
+}
 
+/// like `parse` but skips invalid parameters instead of failing
+///
+/// If a parameter can not be parsed the error is recorded and parsing continues
+/// with the next `;` which is not in a quoted string. The returned parse result
+/// only contains the valid parameters, through it's input still contains the
+/// skipped parts. Only if the type or subtype is invalid an error is returned.
+pub(crate) fn parse_recovering<'a, S: Spec>(input: &'a str)
+    -> Result<(ParseResult<'a>, Vec<ParserErrorRef<'a>>), ParserErrorRef<'a>>
+{
+    let (slash_idx, end_of_type_idx) = parse_media_type_head::<S>(input)?;
+    let plus_idx = find_plus_idx(input, slash_idx, end_of_type_idx);
+    let mut params: Vec<ParamIndices> = Vec::new();
+    let mut errors = Vec::new();
+    let mut offset = end_of_type_idx;
+    // the index of the next parameter, counting skipped (invalid) parameters
+    let mut param_index = 0;
+    loop {
+        let skip_from = match parse_param_sep::<S>(input, offset) {
+            Ok(Some(param_start)) => {
+                param_index += 1;
+                match parse_param::<S>(input, param_start) {
+                    Ok(indices) => {
+                        offset = indices.end;
+                        params.push(indices);
+                        continue;
+                    },
+                    Err(err) => {
+                        errors.push(err.in_param(param_index - 1));
+                        param_start
+                    }
+                }
+            },
+            Ok(None) => break,
+            Err(err) => {
+                // a separator error directly follows the subtype or a valid parameter
+                let prev_param = params.last().map(|indices| (param_index - 1, indices));
+                errors.push(separator_error(err, prev_param));
+                offset
+            }
+        };
+        match find_unquoted(input, skip_from, b';') {
+            Some(next_sc_idx) => offset = next_sc_idx,
+            None => break
+        }
+    }
+    let pres = ParseResult { input, slash_idx, plus_idx, end_of_type_idx, params };
+    Ok((pres, errors))
+}
 
 fn parse_media_type_params<S: Spec>(input: &str, offset: usize)
     -> Result<Vec<ParamIndices>, ParserErrorRef>
 {
-    let mut out = Vec::new();
+    let mut out: Vec<ParamIndices> = Vec::new();
     let mut offset = offset;
-    loop {
-        //1. parse ws
-        let sc_idx = at_pos!(offset do S::parse_space | input );
-        //2. if tail end { break }
-        if sc_idx == input.len() { break }
-        //3. parse ;
-        let after_sc_idx = parse_ascii_char(input, sc_idx, b';')?;
-        //4. parse ws
-        let param_name_start = at_pos!(after_sc_idx do S::parse_space | input);
-        //5. parse token
-        let param_eq_idx = at_pos!(param_name_start do S::parse_token | input);
-        //6. parse =
-        let param_value_start = parse_ascii_char(input, param_eq_idx, b'=')?;
-        //7. if next == '"' { parse quoted_value } else { parse unquoed_value }
-        let param_end_idx;
-        if input.as_bytes().get(param_value_start) == Some(&b'"') {
-            param_end_idx = at_pos!(param_value_start do S::parse_quoted_string | input);
-        } else {
-            param_end_idx = at_pos!(param_value_start do S::parse_unquoted_value | input);
-        }
+    while let Some(param_start) = parse_param_sep::<S>(input, offset)
+        .map_err(|err| separator_error(err, out.last().map(|indices| (out.len() - 1, indices))))?
+    {
+        let indices = parse_param::<S>(input, param_start)
+            .map_err(|err| err.in_param(out.len()))?;
+        offset = indices.end;
+        out.push(indices);
+    }
+    Ok(out)
+}
 
-        out.push(ParamIndices {
-            start: param_name_start,
-            eq_idx: param_eq_idx,
-            end: param_end_idx
-        });
+/// parses the white space and `;` in front of a parameter starting at `offset`
+///
+/// Returns the index after the `;` or `None` if there is nothing but
+/// (spec specific) white space left in the input.
+fn parse_param_sep<S: Spec>(input: &str, offset: usize) -> Result<Option<usize>, ParserErrorRef> {
+    //1. parse ws
+    let sc_idx = at_pos!(offset do S::parse_space | input);
+    //2. if tail end { break }
+    if sc_idx == input.len() { return Ok(None) }
+    //3. parse ;
+    Ok(Some(parse_ascii_char(input, sc_idx, b';')?))
+}
 
-        offset = param_end_idx;
+/// adds context to a error returned by `parse_param_sep`
+///
+/// The separator belongs to the part before it, i.e. to the subtype or to the
+/// value of the previous parameter (given with it's index). Errors in comments
+/// are kept as they are.
+fn separator_error<'a>(err: ParserErrorRef<'a>, prev_param: Option<(usize, &ParamIndices)>)
+    -> ParserErrorRef<'a>
+{
+    match prev_param {
+        _ if err.component().is_some() => err,
+        None => err.in_component(Component::Subtype),
+        Some((index, indices)) => err.in_component(Component::ParamValue)
+            .with_param_name(indices.start, indices.eq_idx)
+            .in_param(index)
     }
+}
 
-    Ok(out)
+/// parses a `name=value` parameter starting at `after_sc_idx`, i.e. after the `;`
+fn parse_param<S: Spec>(input: &str, after_sc_idx: usize)
+    -> Result<ParamIndices, ParserErrorRef>
+{
+    //4. parse ws
+    let param_name_start = at_pos!(after_sc_idx do S::parse_space | input; Component::ParamName);
+    //5. parse token
+    let param_eq_idx = at_pos!(param_name_start do S::parse_token | input; Component::ParamName);
+    //6. parse =
+    let param_value_start = parse_ascii_char(input, param_eq_idx, b'=')
+        .map_err(|err| err.in_component(Component::ParamName))
+        .map_err(|err| err.with_param_name(param_name_start, param_eq_idx))?;
+    //7. if next == '"' { parse quoted_value } else { parse unquoed_value }
+    let param_end_idx = parse_param_value::<S>(input, param_value_start)
+        .map_err(|err| err.with_param_name(param_name_start, param_eq_idx))?;
+
+    Ok(ParamIndices {
+        start: param_name_start,
+        eq_idx: param_eq_idx,
+        end: param_end_idx
+    })
+}
 
+fn parse_param_value<S: Spec>(input: &str, value_start: usize) -> Result<usize, ParserErrorRef> {
+    if input.as_bytes().get(value_start) == Some(&b'"') {
+        Ok(at_pos!(value_start do S::parse_quoted_string | input; Component::ParamValue))
+    } else {
+        Ok(at_pos!(value_start do S::parse_unquoted_value | input; Component::ParamValue))
+    }
 }
 
 
@@ -109,7 +281,13 @@ fn parse_media_type_params<S: Spec>(input: &str, offset: usize)
 mod test {
 
     use ::spec::{HttpSpec, Obs};
-    use super::{parse, ParseResult, ParamIndices};
+    use ::spec::{StrictSpec, MimeSpec, AnySpec};
+    use quoted_string::error::CoreError;
+    use error::{ErrorKind, ExpectedChar, Component};
+    use super::{
+        parse, parse_range, parse_range_at, parse_recovering, find_plus_idx,
+        ParseResult, ParamIndices
+    };
     #[cfg(all(feature="inner-bench", test))]
     use super::parse_media_type_head;
 
@@ -122,6 +300,7 @@ mod test {
     fn parse_charset_utf8() {
         let pres: ParseResult = assert_ok!(parse::<HttpSpec<Obs>>("text/plain; charset=utf-8"));
         assert_eq!(pres.slash_idx, 4);
+        assert_eq!(pres.plus_idx, 10);
         assert_eq!(pres.end_of_type_idx, 10);
         assert_eq!(pres.params, vec![ParamIndices {
             start: 12,
@@ -130,6 +309,185 @@ mod test {
         }]);
     }
 
+    #[test]
+    fn errors_are_relative_to_input() {
+        let err = parse::<HttpSpec<Obs>>("text/plain; a=b; c d").unwrap_err();
+        assert_eq!(err.input(), "text/plain; a=b; c d");
+        assert_eq!(err.kind(), ErrorKind::UnexpectedChar {
+            pos: 18, expected: ExpectedChar::Char('=')
+        });
+        assert_eq!(err.component(), Some(Component::ParamName));
+    }
+
+    #[test]
+    fn unexpected_eof_has_position() {
+        let err = parse::<HttpSpec<Obs>>("text").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof { pos: 4, expected: ExpectedChar::Char('/') });
+
+        let err = parse::<HttpSpec<Obs>>("text/plain; a").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof { pos: 13, expected: ExpectedChar::Char('=') });
+
+        let err = parse::<HttpSpec<Obs>>("text/plain; a=\"b;c").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof { pos: 14, expected: ExpectedChar::Char('"') });
+        assert_eq!(err.component(), Some(Component::ParamValue));
+
+        let err = parse::<MimeSpec>("text/plain; (a comment").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedEof { pos: 12, expected: ExpectedChar::Char(')') });
+        assert_eq!(err.component(), Some(Component::Comment));
+    }
+
+    #[test]
+    fn errors_have_param_context() {
+        let err = parse::<HttpSpec<Obs>>("text/plain; a=b; boundary=[x]").unwrap_err();
+        assert_eq!(err.component(), Some(Component::ParamValue));
+        assert_eq!(err.param_index(), Some(1));
+        assert_eq!(err.param_name(), Some("boundary"));
+
+        let err = parse::<HttpSpec<Obs>>("text/plain; a=b; c d").unwrap_err();
+        assert_eq!(err.param_index(), Some(1));
+        assert_eq!(err.param_name(), Some("c"));
+
+        let err = parse::<HttpSpec<Obs>>("text/plain; [=b").unwrap_err();
+        assert_eq!(err.param_index(), Some(0));
+        assert_eq!(err.param_name(), None);
+
+        let err = parse::<HttpSpec<Obs>>("te[t/plain; a=b").unwrap_err();
+        assert_eq!(err.param_index(), None);
+        assert_eq!(err.param_name(), None);
+    }
+
+    #[test]
+    fn parse_recovering_errors_have_param_context() {
+        let (_, errors) = assert_ok!(parse_recovering::<HttpSpec<Obs>>("text/plain; a=[; b=c; d=]"));
+        let context = errors.iter()
+            .map(|err| (err.param_index(), err.param_name()))
+            .collect::<Vec<_>>();
+        assert_eq!(context, vec![(Some(0), Some("a")), (Some(2), Some("d"))]);
+    }
+
+    #[test]
+    fn errors_have_components() {
+        let component = |input| parse::<MimeSpec>(input).unwrap_err().component();
+        assert_eq!(component("te[t/plain"), Some(Component::Type));
+        assert_eq!(component("text/pl[in"), Some(Component::Subtype));
+        assert_eq!(component("text/[plain"), Some(Component::Subtype));
+        assert_eq!(component("text/plain; a=[b"), Some(Component::ParamValue));
+        assert_eq!(component("text/plain; (a\x01) a=b"), Some(Component::Comment));
+    }
+
+    #[test]
+    fn junk_after_subtype_is_no_param_error() {
+        let err = parse::<MimeSpec>("text/pl[in").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedChar { pos: 7, expected: ExpectedChar::Char(';') });
+        assert_eq!(err.component(), Some(Component::Subtype));
+        assert_eq!(err.param_index(), None);
+
+        let err = parse::<AnySpec>("text/pl\u{e4}in").unwrap_err();
+        assert_eq!(err.component(), Some(Component::Subtype));
+        assert_eq!(err.param_index(), None);
+
+        let err = parse::<HttpSpec<Obs>>("text/plain; a=b c").unwrap_err();
+        assert_eq!(err.component(), Some(Component::ParamValue));
+        assert_eq!(err.param_index(), Some(0));
+        assert_eq!(err.param_name(), Some("a"));
+    }
+
+    #[test]
+    fn comment_before_param_name() {
+        let pres = assert_ok!(parse::<MimeSpec>("text/plain; (comment) a=b"));
+        assert_eq!(pres.params, vec![ParamIndices { start: 22, eq_idx: 23, end: 25 }]);
+    }
+
+    #[test]
+    fn nested_errors_are_relative_to_input() {
+        let err = parse::<HttpSpec<Obs>>("text/plain; a=b; c=\"d\x01\"").unwrap_err();
+        assert_eq!(err.input(), "text/plain; a=b; c=\"d\x01\"");
+        assert_eq!(err.kind(), ErrorKind::QuotedParamValue { pos: 21, cause: CoreError::InvalidChar });
+    }
+
+    #[test]
+    fn parse_recovering_skips_invalid_params() {
+        let input = "text/plain; a=b; c d; e=\"f;g\" x; h=i";
+        let (pres, errors) = assert_ok!(parse_recovering::<HttpSpec<Obs>>(input));
+        let params = pres.params.iter()
+            .map(|idx| &input[idx.start..idx.end])
+            .collect::<Vec<_>>();
+        assert_eq!(params, vec!["a=b", "e=\"f;g\"", "h=i"]);
+        assert_eq!(errors.len(), 2);
+    }
+
+    #[test]
+    fn parse_recovering_skips_junk_after_type() {
+        let input = "text/plain junk; a=b";
+        let (pres, errors) = assert_ok!(parse_recovering::<HttpSpec<Obs>>(input));
+        assert_eq!(pres.end_of_type_idx, 10);
+        assert_eq!(pres.params, vec![ParamIndices { start: 17, eq_idx: 18, end: 20 }]);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind(), ErrorKind::UnexpectedChar {
+            pos: 11, expected: ExpectedChar::Char(';')
+        });
+        assert_eq!(errors[0].component(), Some(Component::Subtype));
+        assert_eq!(errors[0].param_index(), None);
+    }
+
+    #[test]
+    fn parse_recovering_fails_on_invalid_head() {
+        assert!(parse_recovering::<HttpSpec<Obs>>("text; a=b").is_err());
+    }
+
+    #[test]
+    fn parse_suffix() {
+        let pres = assert_ok!(parse::<HttpSpec<Obs>>("application/vnd.foo+json; a=b"));
+        assert_eq!(pres.slash_idx, 11);
+        assert_eq!(pres.plus_idx, 19);
+        assert_eq!(pres.end_of_type_idx, 24);
+    }
+
+    #[test]
+    fn plus_idx_needs_chars_on_both_sides() {
+        assert_eq!(find_plus_idx("a/b+c+d", 1, 7), 5);
+        assert_eq!(find_plus_idx("a/+d", 1, 4), 4);
+        assert_eq!(find_plus_idx("a/b+", 1, 4), 4);
+        assert_eq!(find_plus_idx("a/b", 1, 3), 3);
+    }
+
+    #[test]
+    fn parse_range_wildcards() {
+        let pres = assert_ok!(parse_range::<HttpSpec<Obs>>("*/*; q=1"));
+        assert_eq!(pres.slash_idx, 1);
+        assert_eq!(pres.end_of_type_idx, 3);
+        let pres = assert_ok!(parse_range::<HttpSpec<Obs>>("text/*"));
+        assert_eq!(pres.slash_idx, 4);
+        assert_eq!(pres.end_of_type_idx, 6);
+    }
+
+    #[test]
+    fn parse_range_at_offset() {
+        let pres = assert_ok!(parse_range_at::<HttpSpec<Obs>>("a/b, text/*; x=y", 5));
+        assert_eq!(pres.slash_idx, 9);
+        assert_eq!(pres.end_of_type_idx, 11);
+        assert_eq!(pres.params, vec![ParamIndices { start: 13, eq_idx: 14, end: 16 }]);
+        let pres = pres.rebase(5);
+        assert_eq!(pres.input, "text/*; x=y");
+        assert_eq!(pres.slash_idx, 4);
+        assert_eq!(pres.params, vec![ParamIndices { start: 8, eq_idx: 9, end: 11 }]);
+    }
+
+    #[test]
+    fn parse_range_wildcard_if_not_a_token_char() {
+        assert!(parse::<StrictSpec>("*/*").is_err());
+        let pres = assert_ok!(parse_range::<StrictSpec>("*/*"));
+        assert_eq!(pres.end_of_type_idx, 3);
+    }
+
+    #[test]
+    fn parse_range_rejects_wildcard_type_with_subtype() {
+        let err = parse_range::<HttpSpec<Obs>>("*/json").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedChar {
+            pos: 2, expected: ExpectedChar::Char('*')
+        });
+    }
+
 
 
 
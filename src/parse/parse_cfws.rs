@@ -1,4 +1,4 @@
-use error::{ErrorKind, ParserErrorRef, ExpectedChar};
+use error::{ErrorKind, ParserErrorRef, ExpectedChar, Component};
 
 
 use lut::Table;
@@ -13,8 +13,10 @@ pub fn parse_opt_cfws<E: MimeParsingExt>(input: &str) -> Result<usize, ParserErr
     let mut offset = 0;
     loop {
         offset = parse_opt_fws::<E>(input, offset)?;
-        if let Some(new_offset) = opt_parse_comment::<E>(&input[offset..])? {
-            offset = new_offset;
+        let comment = opt_parse_comment::<E>(&input[offset..])
+            .map_err(|err| err.rebased(input, offset).in_component(Component::Comment))?;
+        if let Some(comment_len) = comment {
+            offset += comment_len;
         } else {
             return Ok(offset);
         }
@@ -77,6 +79,7 @@ fn inner_parse_comment<E>(input: &str, offset: usize) -> Result<usize, ParserErr
     // ccontent =  ctext / quoted-pair / comment
     // FWS      =  ([*WSP "\r\n"] 1*WSP) /  obs-FWS
     // obs-FWS  =  1*([CRLF] WSP)
+    let comment_start = offset - 1;
     let mut offset = offset;
     loop {
         offset = parse_opt_fws::<E>(input, offset)?;
@@ -95,6 +98,9 @@ fn inner_parse_comment<E>(input: &str, offset: usize) -> Result<usize, ParserErr
                     offset = inner_parse_comment::<E>(input, offset)?;
                 },
                 b'\\' => {
+                    if offset >= input.len() {
+                        return Err(unterminated_comment(input, comment_start));
+                    }
                     offset = parse_quotable::<E>(input, offset)?;
                 },
                 b')' => {
@@ -119,11 +125,17 @@ fn inner_parse_comment<E>(input: &str, offset: usize) -> Result<usize, ParserErr
                 }
             }
         } else {
-            return Err(ErrorKind::UnexpectedEof.with_input(input));
+            return Err(unterminated_comment(input, comment_start));
         }
     }
 }
 
+fn unterminated_comment(input: &str, comment_start: usize) -> ParserErrorRef {
+    ErrorKind::UnexpectedEof {
+        pos: comment_start, expected: ExpectedChar::Char(')')
+    }.with_input(input)
+}
+
 fn parse_quotable<E: MimeParsingExt>(input: &str, offset: usize) -> Result<usize, ParserErrorRef>  {
     if let Some(&byte) = input.as_bytes().get(offset) {
         let valid =
@@ -135,7 +147,7 @@ fn parse_quotable<E: MimeParsingExt>(input: &str, offset: usize) -> Result<usize
         if valid {
             Ok(offset + 1)
         } else {
-            let charclass = if E::OBS { "quotable/obs-quotabe" } else { "quotable" };
+            let charclass = if E::OBS { "quotable/obs-quotable" } else { "quotable" };
             Err(
                 ErrorKind::UnexpectedChar {
                     pos: offset, expected: ExpectedChar::CharClass(charclass)
@@ -143,7 +155,10 @@ fn parse_quotable<E: MimeParsingExt>(input: &str, offset: usize) -> Result<usize
             )
         }
     } else {
-        Err(ErrorKind::UnexpectedEof.with_input(input))
+        let charclass = if E::OBS { "quotable/obs-quotable" } else { "quotable" };
+        Err(ErrorKind::UnexpectedEof {
+            pos: offset, expected: ExpectedChar::CharClass(charclass)
+        }.with_input(input))
     }
 }
 
@@ -291,6 +306,19 @@ mod test {
             );
         }
 
+        #[test]
+        fn unterminated() {
+            let eof = |pos| ErrorKind::UnexpectedEof { pos, expected: ExpectedChar::Char(')') };
+            let res = opt_parse_comment::<MimeParsing>("(abc");
+            assert_eq!(res, Err(eof(0).with_input("(abc")));
+            let res = opt_parse_comment::<MimeParsing>("(a (b) c");
+            assert_eq!(res, Err(eof(0).with_input("(a (b) c")));
+            let res = opt_parse_comment::<MimeParsing>("(a (b c");
+            assert_eq!(res, Err(eof(3).with_input("(a (b c")));
+            let res = opt_parse_comment::<MimeObsParsing>("(a \\");
+            assert_eq!(res, Err(eof(0).with_input("(a \\")));
+        }
+
         #[test]
         fn not_a_comment() {
             let text = "  (noop)";
@@ -6,11 +6,50 @@ use error::{ParserErrorRef, ErrorKind, ExpectedChar};
 #[inline]
 pub fn parse_ascii_char(input: &str, pos: usize, bch: u8) -> Result<usize, ParserErrorRef> {
     debug_assert!(bch <= 0x7f, "bch should be an ascii char");
-    if input.as_bytes().get(pos) != Some(&bch) {
+    if pos >= input.len() {
+        Err(ErrorKind::UnexpectedEof {
+            pos, expected: ExpectedChar::Char(bch as char)
+        }.with_input(input))
+    } else if input.as_bytes()[pos] != bch {
         Err(ErrorKind::UnexpectedChar {
             pos, expected: ExpectedChar::Char(bch as char)
         }.with_input(input))
     } else {
         Ok(pos+1)
     }
-}
\ No newline at end of file
+}
+
+/// returns the index of the first `bch` at or after `start` which is not in a quoted string
+///
+/// Quoted pairs (`\"`) in quoted strings are skipped, if there is no such `bch`
+/// `None` is returned.
+pub fn find_unquoted(input: &str, start: usize, bch: u8) -> Option<usize> {
+    debug_assert!(bch != b'"' && bch != b'\\', "bch can not be a quote or escape");
+    let bytes = input.as_bytes();
+    let mut in_quotes = false;
+    let mut idx = start;
+    while idx < bytes.len() {
+        match bytes[idx] {
+            b'\\' if in_quotes => idx += 1,
+            b'"' => in_quotes = !in_quotes,
+            other if other == bch && !in_quotes => return Some(idx),
+            _ => {}
+        }
+        idx += 1;
+    }
+    None
+}
+
+#[cfg(test)]
+mod test {
+    use super::find_unquoted;
+
+    #[test]
+    fn find_unquoted_skips_quoted_strings() {
+        assert_eq!(find_unquoted("a;b", 0, b';'), Some(1));
+        assert_eq!(find_unquoted("a;b;c", 2, b';'), Some(3));
+        assert_eq!(find_unquoted(r#"a="b;c";d"#, 0, b';'), Some(7));
+        assert_eq!(find_unquoted(r#"a="b\";c";d"#, 0, b';'), Some(9));
+        assert_eq!(find_unquoted(r#"a="b;c"#, 0, b';'), None);
+    }
+}
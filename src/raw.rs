@@ -0,0 +1,271 @@
+//! parsing media types from raw bytes, e.g. http header values
+use std::borrow::Cow;
+use std::marker::PhantomData;
+use std::str;
+use std::slice;
+
+use error::{Error, ErrorKind, ExpectedChar};
+use spec::Spec;
+use parse::{parse, find_plus_idx, ParamIndices, ParseResult};
+use name::Name;
+use value::Value;
+use media_type::MediaType;
+
+/// A media type parsed from raw bytes
+///
+/// Under specs allowing obs-text (`HttpSpec<Obs>`, `AnySpec`) quoted parameter
+/// values can contain bytes which are not valid utf-8, such bytes are kept as
+/// they are. Like with `MediaType` the type, subtype and parameter names are
+/// lowercased.
+#[derive(Clone, Debug)]
+pub struct RawMediaType<S: Spec> {
+    buffer: Vec<u8>,
+    slash_idx: usize,
+    plus_idx: usize,
+    end_of_type: usize,
+    params: Vec<ParamIndices>,
+    _spec: PhantomData<S>
+}
+
+impl<S> RawMediaType<S>
+    where S: Spec
+{
+    /// parses the media type from bytes
+    ///
+    /// # Error
+    ///
+    /// If the input is valid utf-8 the error is the same as for `MediaType::parse`.
+    /// Else, if `S` allows obs-text, the input of the error is the input decoded
+    /// as latin-1 (i.e. each byte is one char) and if `S` doesn't allow obs-text
+    /// a `UnexpectedChar` error pointing to the first invalid utf-8 byte is returned.
+    pub fn parse(input: &[u8]) -> Result<Self, Error> {
+        match str::from_utf8(input) {
+            Ok(input) => {
+                let pres = parse::<S>(input)?;
+                Ok(Self::from_parse_result(input.as_bytes(), pres, |idx| idx))
+            }
+            Err(ref err) if !S::allows_obs_text() => {
+                Err(ErrorKind::UnexpectedChar {
+                    pos: err.valid_up_to(),
+                    expected: ExpectedChar::CharClass("utf-8")
+                }.with_input(&String::from_utf8_lossy(input)).to_owned())
+            }
+            Err(_) => {
+                let latin1 = input.iter().map(|&bch| bch as char).collect::<String>();
+                let pres = parse::<S>(&latin1)?;
+                Ok(Self::from_parse_result(input, pres, |idx| latin1[..idx].chars().count()))
+            }
+        }
+    }
+
+    /// creates the media type from the parse result of the input mapped with `raw_idx`
+    fn from_parse_result<F>(input: &[u8], pres: ParseResult, raw_idx: F) -> Self
+        where F: Fn(usize) -> usize
+    {
+        let slash_idx = raw_idx(pres.slash_idx);
+        let end_of_type = raw_idx(pres.end_of_type_idx);
+        let params = pres.params.iter()
+            .map(|indices| ParamIndices {
+                start: raw_idx(indices.start),
+                eq_idx: raw_idx(indices.eq_idx),
+                end: raw_idx(indices.end)
+            })
+            .collect::<Vec<_>>();
+        let len = params.last().map(|param| param.end).unwrap_or(end_of_type);
+
+        let mut buffer = input[..len].to_owned();
+        buffer[..end_of_type].make_ascii_lowercase();
+        for param in params.iter() {
+            buffer[param.start..param.eq_idx].make_ascii_lowercase();
+        }
+        let plus_idx = find_plus_idx(pres.input, pres.slash_idx, pres.end_of_type_idx);
+        let plus_idx = raw_idx(plus_idx);
+
+        RawMediaType { buffer, slash_idx, plus_idx, end_of_type, params, _spec: PhantomData }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    pub fn type_(&self) -> Name {
+        Name::new_unchecked(self.ascii_str(0, self.slash_idx))
+    }
+
+    /// the subtype including the suffix, e.g. `svg+xml` for `image/svg+xml`
+    pub fn subtype(&self) -> Name {
+        Name::new_unchecked(self.ascii_str(self.slash_idx + 1, self.end_of_type))
+    }
+
+    /// the structured syntax suffix, see `AnyMediaType::suffix`
+    pub fn suffix(&self) -> Option<Name> {
+        if self.plus_idx + 1 < self.end_of_type {
+            Some(Name::new_unchecked(self.ascii_str(self.plus_idx + 1, self.end_of_type)))
+        } else {
+            None
+        }
+    }
+
+    pub fn full_type(&self) -> Name {
+        Name::new_unchecked(self.ascii_str(0, self.end_of_type))
+    }
+
+    pub fn params(&self) -> RawParams {
+        RawParams { source: &self.buffer, iter: self.params.iter() }
+    }
+
+    pub fn get_param<'a, N>(&'a self, attr: N) -> Option<RawValue<'a>>
+        where N: PartialEq<Name<'a>>
+    {
+        self.params()
+            .find(|nv| attr == nv.0)
+            .map(|(_name, value)| value)
+    }
+
+    /// converts it to a `MediaType` if it's valid utf-8
+    pub fn to_media_type(&self) -> Option<MediaType<S>> {
+        str::from_utf8(&self.buffer).ok()
+            .map(|input| MediaType::parse(input).expect("[BUG] reparsing valid media type failed"))
+    }
+
+    /// type, subtype and parameter names are tokens which are always ascii
+    fn ascii_str(&self, start: usize, end: usize) -> &str {
+        str::from_utf8(&self.buffer[start..end]).expect("[BUG] tokens are ascii")
+    }
+}
+
+/// A parameter value of a `RawMediaType`, which might not be valid utf-8
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct RawValue<'a> {
+    source: &'a [u8]
+}
+
+impl<'a> RawValue<'a> {
+
+    /// returns the underlying representation, see `Value::as_str_repr`
+    pub fn as_bytes_repr(&self) -> &'a [u8] {
+        self.source
+    }
+
+    #[inline]
+    pub fn is_quoted(&self) -> bool {
+        self.source.first() == Some(&b'"')
+    }
+
+    /// returns the content, i.e. the value without surrounding quotes and quoted-pairs
+    pub fn to_content(&self) -> Cow<'a, [u8]> {
+        if !self.is_quoted() {
+            return Cow::Borrowed(self.source);
+        }
+        let inner = &self.source[1..self.source.len() - 1];
+        if !inner.contains(&b'\\') {
+            return Cow::Borrowed(inner);
+        }
+        let mut content = Vec::with_capacity(inner.len());
+        let mut escaped = false;
+        for &bch in inner {
+            if bch == b'\\' && !escaped {
+                escaped = true;
+            } else {
+                content.push(bch);
+                escaped = false;
+            }
+        }
+        Cow::Owned(content)
+    }
+
+    /// returns the value as `Value` if it's valid utf-8
+    pub fn as_value(&self) -> Option<Value<'a>> {
+        str::from_utf8(self.source).ok()
+            .map(Value::new_unchecked)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct RawParams<'a> {
+    source: &'a [u8],
+    iter: slice::Iter<'a, ParamIndices>
+}
+
+impl<'a> Iterator for RawParams<'a> {
+    type Item = (Name<'a>, RawValue<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next()
+            .map(|pidx| {
+                let name = str::from_utf8(&self.source[pidx.start..pidx.eq_idx])
+                    .expect("[BUG] tokens are ascii");
+                let value = &self.source[pidx.eq_idx+1..pidx.end];
+                (Name::new_unchecked(name), RawValue { source: value })
+            })
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<'a> ExactSizeIterator for RawParams<'a> {
+    #[inline]
+    fn len(&self) -> usize {
+        self.iter.len()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::borrow::Cow;
+    use error::{ErrorKind, ExpectedChar};
+    use spec::{HttpSpec, Obs, Modern, MimeSpec};
+    use super::RawMediaType;
+
+    #[test]
+    fn utf8_input() {
+        let mt = RawMediaType::<HttpSpec<Obs>>::parse(b"Text/Plain; Charset=utf-8").unwrap();
+        assert_eq!(mt.as_bytes(), b"text/plain; charset=utf-8");
+        assert_eq!(mt.full_type(), "text/plain");
+        let value = mt.get_param("charset").unwrap();
+        assert_eq!(value.as_bytes_repr(), b"utf-8");
+        assert_eq!(value.as_value().unwrap().as_str_repr(), "utf-8");
+        let media_type = mt.to_media_type().unwrap();
+        assert_eq!(media_type.as_str_repr(), "text/plain; charset=utf-8");
+    }
+
+    #[test]
+    fn obs_text_is_kept() {
+        let input = b"text/plain; a=\"\xE4\\yx\"; b=c";
+        let mt = RawMediaType::<HttpSpec<Obs>>::parse(input).unwrap();
+        assert_eq!(mt.as_bytes(), &input[..]);
+        let params = mt.params()
+            .map(|(name, value)| (name.as_str(), value.as_bytes_repr()))
+            .collect::<Vec<_>>();
+        assert_eq!(params, vec![("a", &b"\"\xE4\\yx\""[..]), ("b", &b"c"[..])]);
+
+        let value = mt.get_param("a").unwrap();
+        assert!(value.is_quoted());
+        assert_eq!(value.as_value(), None);
+        assert_eq!(value.to_content(), Cow::Owned::<[u8]>(b"\xE4yx".to_vec()));
+        assert_eq!(mt.to_media_type(), None);
+    }
+
+    #[test]
+    fn obs_text_positions_in_error() {
+        let err = RawMediaType::<HttpSpec<Obs>>::parse(b"text/plain; a=\"\xE4\"; b c").unwrap_err();
+        assert_eq!(err.input(), "text/plain; a=\"\u{e4}\"; b c");
+        assert_eq!(err.caret_display().to_string(), concat!(
+            "text/plain; a=\"\u{e4}\"; b c\n",
+            "                    ^ parsing hit an unexpected character (in parameter name of `b`)"
+        ));
+    }
+
+    #[test]
+    fn obs_text_not_allowed() {
+        let err = RawMediaType::<HttpSpec<Modern>>::parse(b"text/plain; a=\"\xE4\"").unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::UnexpectedChar {
+            pos: 15, expected: ExpectedChar::CharClass("utf-8")
+        });
+        assert!(RawMediaType::<MimeSpec>::parse(b"text/plain; a=\"\xE4\"").is_err());
+        assert!(RawMediaType::<HttpSpec<Obs>>::parse(b"text/pl\xE4in").is_err());
+    }
+}
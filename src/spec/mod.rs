@@ -1,6 +1,7 @@
 use std::marker::PhantomData;
 use std::fmt::Debug;
 use std::default::Default;
+use std::borrow::Cow;
 
 use error::{ParserErrorRef, ErrorKind, ExpectedChar};
 use seal::Seal;
@@ -20,17 +21,59 @@ pub trait Spec: Seal + GeneralQSSpec {
     fn parse_token(input: &str) -> Result<usize, ParserErrorRef>;
     fn parse_space(input: &str) -> Result<usize, ParserErrorRef>;
 
+    /// the max length of a generated `name=value` parameter
+    ///
+    /// Longer parameters are split into RFC 2231 continuations
+    /// (`name*0=..; name*1=..`) when generating media types. If
+    /// `None` is returned parameters are never split.
+    fn param_split_threshold() -> Option<usize> {
+        None
+    }
+
+    /// true if quoted strings can contain obs-text (`0x80`-`0xFF`) which is not valid utf-8
+    ///
+    /// This is only relevant when parsing raw bytes (see `RawMediaType`).
+    fn allows_obs_text() -> bool {
+        false
+    }
+
+    /// unfolds a (header field) value which might contain folding white space
+    ///
+    /// By default nothing is unfolded, so folded values won't parse. Http specs
+    /// allowing obsolete syntax replace obs-folds with spaces (RFC 7230 section 3.2.4),
+    /// mime specs remove each CRLF followed by white space (RFC 5322 section 2.2.3).
+    fn unfold(input: &str) -> Cow<str> {
+        Cow::Borrowed(input)
+    }
+
+    /// parses the type of a media type, by default it's parsed as token
+    fn parse_type(input: &str) -> Result<usize, ParserErrorRef> {
+        Self::parse_token(input)
+    }
+
+    /// parses the subtype (incl. suffix) of a media type, by default it's parsed as token
+    fn parse_subtype(input: &str) -> Result<usize, ParserErrorRef> {
+        Self::parse_token(input)
+    }
+
     fn validate_token(input: &str) -> Result<(), ParserErrorRef> {
         let end = Self::parse_token(input)?;
-        debug_assert!(end <= input.len(), "end is a index in input, so it's <= input.len()");
-        if end == input.len() {
-            Ok(())
-        } else {
-            Err(ErrorKind::UnexpectedChar {
-                pos: end,
-                expected: ExpectedChar::CharClass("token char")
-            }.with_input(input))
-        }
+        validate_complete(input, end)
+    }
+
+    fn validate_type(input: &str) -> Result<(), ParserErrorRef> {
+        let end = Self::parse_type(input)?;
+        validate_complete(input, end)
+    }
+
+    fn validate_subtype(input: &str) -> Result<(), ParserErrorRef> {
+        let end = Self::parse_subtype(input)?;
+        validate_complete(input, end)
+    }
+
+    fn validate_unquoted_value(input: &str) -> Result<(), ParserErrorRef> {
+        let end = Self::parse_unquoted_value(input)?;
+        validate_complete(input, end)
     }
 
     fn parse_unquoted_value(input: &str) -> Result<usize, ParserErrorRef> {
@@ -43,6 +86,12 @@ pub trait Spec: Seal + GeneralQSSpec {
         match qs_parse::<Self>(input) {
             //we just want the offset
             Ok(pres) => Ok(pres.quoted_string.len()),
+            Err((pos, CoreError::DoesNotEndWithDQuotes)) if pos == input.len() => {
+                //the quoted string starts at 0
+                Err(ErrorKind::UnexpectedEof {
+                    pos: 0, expected: ExpectedChar::Char('"')
+                }.with_input(input))
+            },
             Err((pos, cause)) => {
                 Err(ErrorKind::QuotedParamValue { pos, cause }.with_input(input))
             }
@@ -51,17 +100,58 @@ pub trait Spec: Seal + GeneralQSSpec {
     }
 }
 
+fn validate_complete(input: &str, end: usize) -> Result<(), ParserErrorRef> {
+    debug_assert!(end <= input.len(), "end is a index in input, so it's <= input.len()");
+    if end == input.len() {
+        Ok(())
+    } else {
+        Err(ErrorKind::UnexpectedChar {
+            pos: end,
+            expected: ExpectedChar::CharClass("token char")
+        }.with_input(input))
+    }
+}
+
+/// Marker for specs which allow folding white space (e.g. `"\r\n "`) between parameters
+///
+/// This is the case for all mime specs and the `AnySpec`.
+pub trait FoldingSpec: Spec {}
 
-pub trait ObsNormalSwitch: Seal+Copy+Clone+Debug {}
+pub trait ObsNormalSwitch: Seal+Copy+Clone+Debug {
+    /// true if obsolete syntax is allowed
+    const OBS: bool;
+}
 pub trait InternationalizedSwitch: Seal+Copy+Clone+Debug {}
 
+/// Determines when parameters of generated `MimeSpec` media types are split.
+///
+/// Mails have a line length limit of 78 (soft) and 998 (hard) chars (RFC 5322),
+/// to not hit this limit long parameters are split into RFC 2231 continuations.
+///
+/// This trait is not sealed, so a custom threshold can be used by implementing it.
+pub trait SplitThreshold: Copy+Clone+Debug+Default {
+    /// the max length of a `name=value` parameter, `None` means parameters are never split
+    fn max_param_len() -> Option<usize>;
+}
+
+/// Spec for media types used in mails (RFC 2045, RFC 2231, RFC 6532)
+///
+/// # Breaking Change
+///
+/// By default (`SplitAt78`) generated parameters which are longer then a
+/// mail line are now split into RFC 2231 continuations, before this they were
+/// never split. Use `MimeSpec<_, _, NoSplit>` to get the previous output.
 #[derive(Copy, Clone, Debug, Default)]
 pub struct MimeSpec<
     TP: InternationalizedSwitch = Internationalized,
-    O: ObsNormalSwitch = Obs
->(PhantomData<(TP,O)>);
+    O: ObsNormalSwitch = Obs,
+    ST: SplitThreshold = SplitAt78
+>(PhantomData<(TP,O,ST)>);
 
-impl<T: InternationalizedSwitch, O: ObsNormalSwitch> Seal for MimeSpec<T, O> {}
+impl<T: InternationalizedSwitch, O: ObsNormalSwitch, ST: SplitThreshold> Seal for MimeSpec<T, O, ST> {}
+impl<T, O, ST> FoldingSpec for MimeSpec<T, O, ST>
+    where T: InternationalizedSwitch, O: ObsNormalSwitch, ST: SplitThreshold, MimeSpec<T, O, ST>: Spec
+{}
 
 #[derive(Copy, Clone, Debug, Default)]
 pub struct HttpSpec<
@@ -74,6 +164,15 @@ impl<O: ObsNormalSwitch> Seal for HttpSpec<O> {}
 pub struct StrictSpec;
 impl Seal for StrictSpec {}
 
+/// Like `StrictSpec` but enforces the RFC 6838 registration rules for type and subtype
+///
+/// I.e. type and subtype have to be `restricted-name`s (starting with a alphanumeric
+/// char, containing only alphanumeric chars and `!#$&-^_.+`, at most 127 chars long)
+/// and the type has to be one of the registered top-level types.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct RegistrationSpec;
+impl Seal for RegistrationSpec {}
+
 /// # Note
 ///
 /// Because the AnySpec is meant to be able to parse mimes from "any" spec it has to be able
@@ -82,16 +181,21 @@ impl Seal for StrictSpec {}
 #[derive(Copy, Clone, Debug, Default)]
 pub struct AnySpec;
 impl Seal for AnySpec {}
+impl FoldingSpec for AnySpec {}
 
 #[derive(Copy, Clone, Debug, Default)]
 pub struct Obs;
 impl Seal for Obs {}
-impl ObsNormalSwitch for Obs {}
+impl ObsNormalSwitch for Obs {
+    const OBS: bool = true;
+}
 
 #[derive(Copy, Clone, Debug, Default)]
 pub struct Modern;
 impl Seal for Modern {}
-impl ObsNormalSwitch for Modern {}
+impl ObsNormalSwitch for Modern {
+    const OBS: bool = false;
+}
 
 #[derive(Copy, Clone, Debug, Default)]
 pub struct Ascii;
@@ -103,9 +207,30 @@ pub struct Internationalized;
 impl Seal for Internationalized {}
 impl InternationalizedSwitch for Internationalized {}
 
+/// Split parameters so that `; name=value` fits into a line of 78 chars
+///
+/// This is the default for `MimeSpec`.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SplitAt78;
+impl SplitThreshold for SplitAt78 {
+    fn max_param_len() -> Option<usize> {
+        // 78 - "; ".len()
+        Some(76)
+    }
+}
+
+/// Never split parameters
+#[derive(Copy, Clone, Debug, Default)]
+pub struct NoSplit;
+impl SplitThreshold for NoSplit {
+    fn max_param_len() -> Option<usize> {
+        None
+    }
+}
+
 macro_rules! zs_conversions {
-    ($($tp:ty => $tp2:ty;)*) => ($(
-        impl From<$tp> for $tp2 {
+    ($([$($st:ident),*] $tp:ty => $tp2:ty;)*) => ($(
+        impl<$($st: SplitThreshold),*> From<$tp> for $tp2 {
             fn from(_: $tp) -> $tp2 {
                 Default::default()
             }
@@ -114,25 +239,33 @@ macro_rules! zs_conversions {
 }
 
 zs_conversions! {
-    MimeSpec<Ascii, Obs> => MimeSpec<Internationalized, Obs>;
-    MimeSpec<Ascii, Modern> => MimeSpec<Internationalized, Modern>;
-    MimeSpec<Ascii, Modern> => MimeSpec<Ascii, Obs>;
-    MimeSpec<Ascii, Modern> => MimeSpec<Internationalized, Obs>;
-    MimeSpec<Internationalized, Modern> => MimeSpec<Internationalized, Obs>;
-    HttpSpec<Modern> => HttpSpec<Obs>;
-    StrictSpec => HttpSpec<Modern>;
-    StrictSpec => HttpSpec<Obs>;
-    StrictSpec => MimeSpec<Ascii, Obs>;
-    StrictSpec => MimeSpec<Ascii, Modern>;
-    StrictSpec => MimeSpec<Internationalized, Obs>;
-    StrictSpec => MimeSpec<Internationalized, Modern>;
-    StrictSpec => AnySpec;
-    HttpSpec<Modern> => AnySpec;
-    HttpSpec<Obs> => AnySpec;
-    MimeSpec<Ascii, Obs> => AnySpec;
-    MimeSpec<Ascii, Modern> => AnySpec;
-    MimeSpec<Internationalized, Obs> => AnySpec;
-    MimeSpec<Internationalized, Modern> => AnySpec;
+    [] RegistrationSpec => StrictSpec;
+    [] RegistrationSpec => HttpSpec<Modern>;
+    [] RegistrationSpec => HttpSpec<Obs>;
+    [ST] RegistrationSpec => MimeSpec<Ascii, Obs, ST>;
+    [ST] RegistrationSpec => MimeSpec<Ascii, Modern, ST>;
+    [ST] RegistrationSpec => MimeSpec<Internationalized, Obs, ST>;
+    [ST] RegistrationSpec => MimeSpec<Internationalized, Modern, ST>;
+    [] RegistrationSpec => AnySpec;
+    [ST1, ST2] MimeSpec<Ascii, Obs, ST1> => MimeSpec<Internationalized, Obs, ST2>;
+    [ST1, ST2] MimeSpec<Ascii, Modern, ST1> => MimeSpec<Internationalized, Modern, ST2>;
+    [ST1, ST2] MimeSpec<Ascii, Modern, ST1> => MimeSpec<Ascii, Obs, ST2>;
+    [ST1, ST2] MimeSpec<Ascii, Modern, ST1> => MimeSpec<Internationalized, Obs, ST2>;
+    [ST1, ST2] MimeSpec<Internationalized, Modern, ST1> => MimeSpec<Internationalized, Obs, ST2>;
+    [] HttpSpec<Modern> => HttpSpec<Obs>;
+    [] StrictSpec => HttpSpec<Modern>;
+    [] StrictSpec => HttpSpec<Obs>;
+    [ST] StrictSpec => MimeSpec<Ascii, Obs, ST>;
+    [ST] StrictSpec => MimeSpec<Ascii, Modern, ST>;
+    [ST] StrictSpec => MimeSpec<Internationalized, Obs, ST>;
+    [ST] StrictSpec => MimeSpec<Internationalized, Modern, ST>;
+    [] StrictSpec => AnySpec;
+    [] HttpSpec<Modern> => AnySpec;
+    [] HttpSpec<Obs> => AnySpec;
+    [ST] MimeSpec<Ascii, Obs, ST> => AnySpec;
+    [ST] MimeSpec<Ascii, Modern, ST> => AnySpec;
+    [ST] MimeSpec<Internationalized, Obs, ST> => AnySpec;
+    [ST] MimeSpec<Internationalized, Modern, ST> => AnySpec;
 }
 
 // It would be nicer to have it in parse but it's needed for the default impl
@@ -0,0 +1,106 @@
+const VENDOR_FACET: &str = "vnd.";
+const PERSONAL_FACET: &str = "prs.";
+const UNREGISTERED_FACET: &str = "x.";
+const LEGACY_X_PREFIX: &str = "x-";
+
+/// The registration tree of a media type (RFC 6838 section 3)
+///
+/// The tree is determined by the facet (prefix) of the subtype, e.g.
+/// `application/vnd.ms-excel` is in the vendor tree.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum RegistrationTree<'a> {
+    /// subtypes without facet, e.g. `text/plain`
+    Standards,
+    /// subtypes with the `vnd.` facet, contains the producer
+    ///
+    /// The producer is the part of the subtype after `vnd.` up to
+    /// the next `.`, e.g. `ms-excel` for `vnd.ms-excel` and `oasis`
+    /// for `vnd.oasis.opendocument.text`
+    Vendor(&'a str),
+    /// subtypes with the `prs.` facet
+    Personal,
+    /// subtypes with the `x.` facet
+    Unregistered,
+    /// subtypes with the historic `x-` prefix (RFC 6838 section 3.4)
+    LegacyX
+}
+
+impl<'a> RegistrationTree<'a> {
+
+    /// determines the tree from the subtype (without suffix)
+    ///
+    /// The facets are matched ascii case-insensitive.
+    pub fn from_subtype(subtype: &'a str) -> Self {
+        use self::RegistrationTree::*;
+        if let Some(tail) = strip_prefix_ignore_case(subtype, VENDOR_FACET) {
+            let producer_end = tail.find('.').unwrap_or(tail.len());
+            Vendor(&tail[..producer_end])
+        } else if strip_prefix_ignore_case(subtype, PERSONAL_FACET).is_some() {
+            Personal
+        } else if strip_prefix_ignore_case(subtype, UNREGISTERED_FACET).is_some() {
+            Unregistered
+        } else if strip_prefix_ignore_case(subtype, LEGACY_X_PREFIX).is_some() {
+            LegacyX
+        } else {
+            Standards
+        }
+    }
+
+    /// true for the standards, vendor and personal tree
+    ///
+    /// Media types in the unregistered tree and with the `x-` prefix
+    /// can not be registered with IANA.
+    pub fn is_registrable(&self) -> bool {
+        use self::RegistrationTree::*;
+        match *self {
+            Standards | Vendor(_) | Personal => true,
+            Unregistered | LegacyX => false
+        }
+    }
+}
+
+fn strip_prefix_ignore_case<'a>(input: &'a str, prefix: &str) -> Option<&'a str> {
+    let matches = input.len() >= prefix.len()
+        && input.as_bytes()[..prefix.len()].eq_ignore_ascii_case(prefix.as_bytes());
+    if matches {
+        Some(&input[prefix.len()..])
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::RegistrationTree;
+    use super::RegistrationTree::*;
+
+    #[test]
+    fn standards_tree() {
+        assert_eq!(RegistrationTree::from_subtype("plain"), Standards);
+        assert_eq!(RegistrationTree::from_subtype("vnd"), Standards);
+        assert_eq!(RegistrationTree::from_subtype("xml"), Standards);
+    }
+
+    #[test]
+    fn vendor_tree() {
+        assert_eq!(RegistrationTree::from_subtype("vnd.ms-excel"), Vendor("ms-excel"));
+        assert_eq!(RegistrationTree::from_subtype("vnd.oasis.opendocument.text"), Vendor("oasis"));
+        assert_eq!(RegistrationTree::from_subtype("VND.Foo"), Vendor("Foo"));
+    }
+
+    #[test]
+    fn personal_unregistered_and_legacy() {
+        assert_eq!(RegistrationTree::from_subtype("prs.btif"), Personal);
+        assert_eq!(RegistrationTree::from_subtype("x.foo"), Unregistered);
+        assert_eq!(RegistrationTree::from_subtype("x-gzip"), LegacyX);
+    }
+
+    #[test]
+    fn registrable() {
+        assert!(Standards.is_registrable());
+        assert!(Vendor("foo").is_registrable());
+        assert!(Personal.is_registrable());
+        assert!(!Unregistered.is_registrable());
+        assert!(!LegacyX.is_registrable());
+    }
+}
@@ -10,9 +10,10 @@ pub static UTF8: Value = Value { source: "utf8" };
 
 
 /// A parameter value section of a `Mime`.
-/// 
-/// Except for the `charset` parameter, parameters 
-/// are compared case sensitive
+///
+/// Comparing two `Value`s compares their content case sensitive, when
+/// comparing media types the values of the parameters in
+/// `CASE_INSENSITIVE_PARAMS` (i.e. `charset`) are compared case-insensitive.
 #[derive(Clone, Copy, Hash)]
 pub struct Value<'a> {
     source: &'a str,
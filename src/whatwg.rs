@@ -0,0 +1,265 @@
+//! implementation of the WHATWG mime sniffing standard's "parse a MIME type"
+//! and "serialize a MIME type" algorithms (https://mimesniff.spec.whatwg.org)
+use lut::Table;
+use media_type_impl_utils::lookup_tables::{MediaTypeChars, HttpToken};
+
+use error::{ErrorKind, ExpectedChar, ParserErrorRef};
+use parse::ParamIndices;
+
+const TYPE_SEP: char = '/';
+const PARAM_SEP: char = ';';
+const PARAM_KV_SEP: char = '=';
+const QUOTE: char = '"';
+const ESCAPE: char = '\\';
+
+/// the parts of a parsed media type, the buffer is in it's WHATWG serialized form
+pub(crate) struct WhatwgParts {
+    pub(crate) buffer: String,
+    pub(crate) slash_idx: usize,
+    pub(crate) end_of_type: usize,
+    pub(crate) params: Vec<ParamIndices>
+}
+
+/// parses `input` with the WHATWG "parse a MIME type" algorithm
+///
+/// Parameters which are invalid are skipped and if a parameter appears
+/// multiple times the first one is used. The returned buffer contains the
+/// WHATWG serialization of the media type.
+///
+/// # Error
+///
+/// Like with the WHATWG algorithm only an invalid type or subtype is an error,
+/// the position of the error is relative to the (not trimmed) `input`.
+pub(crate) fn parse(input: &str) -> Result<WhatwgParts, ParserErrorRef> {
+    let start = input.len() - input.trim_start_matches(is_http_ws).len();
+    let end = input.trim_end_matches(is_http_ws).len().max(start);
+
+    let slash_pos = input[start..end].find(TYPE_SEP).map(|idx| start + idx);
+    let type_end = slash_pos.unwrap_or(end);
+    validate_token(input, start, type_end)?;
+    let slash_pos = slash_pos.ok_or_else(|| ErrorKind::UnexpectedChar {
+        pos: end, expected: ExpectedChar::Char(TYPE_SEP)
+    }.with_input(input))?;
+
+    let subtype_start = slash_pos + 1;
+    let mut pos = input[subtype_start..end].find(PARAM_SEP)
+        .map(|idx| subtype_start + idx)
+        .unwrap_or(end);
+    let subtype_end = subtype_start + input[subtype_start..pos].trim_end_matches(is_http_ws).len();
+    validate_token(input, subtype_start, subtype_end)?;
+
+    let mut params: Vec<(String, String)> = Vec::new();
+    while pos < end {
+        // skip the ';' and leading white space
+        pos += 1;
+        pos += input[pos..end].len() - input[pos..end].trim_start_matches(is_http_ws).len();
+
+        let name_end = input[pos..end].find(&[PARAM_SEP, PARAM_KV_SEP][..])
+            .map(|idx| pos + idx)
+            .unwrap_or(end);
+        let name = input[pos..name_end].to_ascii_lowercase();
+        pos = name_end;
+        if pos >= end {
+            break;
+        }
+        if input[pos..].starts_with(PARAM_SEP) {
+            continue;
+        }
+        // skip the '='
+        pos += 1;
+        if pos >= end {
+            break;
+        }
+
+        let value;
+        if input[pos..].starts_with(QUOTE) {
+            let (content, value_end) = collect_quoted_string(input, pos, end);
+            value = content;
+            pos = input[value_end..end].find(PARAM_SEP)
+                .map(|idx| value_end + idx)
+                .unwrap_or(end);
+        } else {
+            let value_end = input[pos..end].find(PARAM_SEP)
+                .map(|idx| pos + idx)
+                .unwrap_or(end);
+            value = input[pos..value_end].trim_end_matches(is_http_ws).to_owned();
+            pos = value_end;
+            if value.is_empty() {
+                continue;
+            }
+        }
+
+        let is_valid = !name.is_empty()
+            && name.chars().all(is_token_char)
+            && value.chars().all(is_quoted_string_token_char)
+            && !params.iter().any(|(existing, _)| *existing == name);
+        if is_valid {
+            params.push((name, value));
+        }
+    }
+
+    let mut buffer = input[start..type_end].to_ascii_lowercase();
+    let slash_idx = buffer.len();
+    buffer.push(TYPE_SEP);
+    buffer.push_str(&input[subtype_start..subtype_end].to_ascii_lowercase());
+    let end_of_type = buffer.len();
+    let params = params.iter()
+        .map(|(name, value)| push_param(&mut buffer, name, value))
+        .collect();
+
+    Ok(WhatwgParts { buffer, slash_idx, end_of_type, params })
+}
+
+/// pushes `;name=value` to the buffer, quoting the value if needed
+///
+/// The value is quoted if it is empty or contains non token chars,
+/// in the quoted form `"` and `\` are escaped with a `\`.
+pub(crate) fn push_param(buffer: &mut String, name: &str, value: &str) -> ParamIndices {
+    buffer.push(PARAM_SEP);
+    let start = buffer.len();
+    buffer.push_str(name);
+    let eq_idx = buffer.len();
+    buffer.push(PARAM_KV_SEP);
+    if !value.is_empty() && value.chars().all(is_token_char) {
+        buffer.push_str(value);
+    } else {
+        buffer.push(QUOTE);
+        for ch in value.chars() {
+            if ch == QUOTE || ch == ESCAPE {
+                buffer.push(ESCAPE);
+            }
+            buffer.push(ch);
+        }
+        buffer.push(QUOTE);
+    }
+    ParamIndices { start, eq_idx, end: buffer.len() }
+}
+
+/// collects a http quoted string starting at the `"` at `start`
+///
+/// Returns the (unescaped) content and the position after the closing quote
+/// (or `end` if there is none).
+fn collect_quoted_string(input: &str, start: usize, end: usize) -> (String, usize) {
+    let mut content = String::new();
+    let mut chars = input[start+1..end].char_indices();
+    while let Some((idx, ch)) = chars.next() {
+        match ch {
+            QUOTE => return (content, start + 1 + idx + 1),
+            ESCAPE => match chars.next() {
+                Some((_, escaped)) => content.push(escaped),
+                None => content.push(ESCAPE)
+            },
+            ch => content.push(ch)
+        }
+    }
+    (content, end)
+}
+
+fn validate_token(input: &str, start: usize, end: usize) -> Result<(), ParserErrorRef> {
+    let invalid = input[start..end].char_indices()
+        .find(|&(_, ch)| !is_token_char(ch))
+        .map(|(idx, _)| start + idx);
+    let pos = match invalid {
+        Some(pos) => pos,
+        None if start == end => start,
+        None => return Ok(())
+    };
+    Err(ErrorKind::UnexpectedChar {
+        pos, expected: ExpectedChar::CharClass("http token char")
+    }.with_input(input))
+}
+
+fn is_http_ws(ch: char) -> bool {
+    ch == ' ' || ch == '\t' || ch == '\r' || ch == '\n'
+}
+
+fn is_token_char(ch: char) -> bool {
+    ch.is_ascii() && MediaTypeChars::check_at(ch as usize, HttpToken)
+}
+
+fn is_quoted_string_token_char(ch: char) -> bool {
+    ch == '\t' || (' ' <= ch && ch <= '~') || ('\u{80}' <= ch && ch <= '\u{ff}')
+}
+
+#[cfg(test)]
+mod test {
+    use error::{ErrorKind, ExpectedChar};
+    use super::parse;
+
+    fn serialized(input: &str) -> String {
+        assert_ok!(parse(input)).buffer
+    }
+
+    fn error_kind(input: &str) -> ErrorKind {
+        match parse(input) {
+            Ok(parts) => panic!("expected Err(..) got Ok({:?})", parts.buffer),
+            Err(err) => err.kind()
+        }
+    }
+
+    #[test]
+    fn simple() {
+        assert_eq!(serialized("text/html"), "text/html");
+        assert_eq!(serialized(" \tTEXT/Html\r\n"), "text/html");
+        assert_eq!(serialized("text/html;charset=gbk"), "text/html;charset=gbk");
+    }
+
+    #[test]
+    fn whitespace_and_empty_params() {
+        assert_eq!(serialized("text/html ;"), "text/html");
+        assert_eq!(serialized("text/html;;; charset=gbk ;"), "text/html;charset=gbk");
+        assert_eq!(serialized("text/html; charset = gbk"), "text/html");
+        assert_eq!(serialized("text/html;charset="), "text/html");
+        assert_eq!(serialized("text/html;charset"), "text/html");
+        assert_eq!(serialized("text/html;charset=gbk ;x=y"), "text/html;charset=gbk;x=y");
+    }
+
+    #[test]
+    fn duplicates_first_wins() {
+        assert_eq!(serialized("text/html;charset=gbk;charset=windows-1255"), "text/html;charset=gbk");
+        assert_eq!(serialized("text/html;CHARSET=gbk;charset=utf-8"), "text/html;charset=gbk");
+        assert_eq!(serialized("text/html;charset=();charset=gbk"), "text/html;charset=\"()\"");
+    }
+
+    #[test]
+    fn quoted_strings() {
+        assert_eq!(serialized("text/html;charset=\"gbk\""), "text/html;charset=gbk");
+        assert_eq!(serialized("text/html;charset=\"gbk"), "text/html;charset=gbk");
+        assert_eq!(serialized("text/html;charset=\"gbk\"x;a=b"), "text/html;charset=gbk;a=b");
+        assert_eq!(serialized("text/html;charset=\"\\\\g\\\"bk\""), "text/html;charset=\"\\\\g\\\"bk\"");
+        assert_eq!(serialized("text/html;charset=\"\""), "text/html;charset=\"\"");
+        assert_eq!(serialized("text/html;a=\"b;c\""), "text/html;a=\"b;c\"");
+        assert_eq!(serialized("text/html;a=\"b\\"), "text/html;a=\"b\\\\\"");
+    }
+
+    #[test]
+    fn invalid_params_are_skipped() {
+        assert_eq!(serialized("text/html;a\u{1}=b;c=d"), "text/html;c=d");
+        assert_eq!(serialized("text/html;a=b\u{100};c=d"), "text/html;c=d");
+        assert_eq!(serialized("text/html;a=\u{ff}"), "text/html;a=\"\u{ff}\"");
+    }
+
+    #[test]
+    fn indices() {
+        let parts = assert_ok!(parse("Text/Html; Charset=gbk; a=\"b c\""));
+        assert_eq!(parts.slash_idx, 4);
+        assert_eq!(parts.end_of_type, 9);
+        let params = parts.params.iter()
+            .map(|idx| (&parts.buffer[idx.start..idx.eq_idx], &parts.buffer[idx.eq_idx+1..idx.end]))
+            .collect::<Vec<_>>();
+        assert_eq!(params, vec![("charset", "gbk"), ("a", "\"b c\"")]);
+    }
+
+    #[test]
+    fn invalid_type_or_subtype() {
+        let token = ExpectedChar::CharClass("http token char");
+        assert_eq!(error_kind("  /html"), ErrorKind::UnexpectedChar { pos: 2, expected: token });
+        assert_eq!(error_kind("te(t/html"), ErrorKind::UnexpectedChar { pos: 2, expected: token });
+        assert_eq!(error_kind("text/ ;a=b"), ErrorKind::UnexpectedChar { pos: 5, expected: token });
+        assert_eq!(error_kind("text/h tml"), ErrorKind::UnexpectedChar { pos: 6, expected: token });
+        assert_eq!(error_kind("text "), ErrorKind::UnexpectedChar {
+            pos: 4, expected: ExpectedChar::Char('/')
+        });
+        assert_eq!(error_kind(""), ErrorKind::UnexpectedChar { pos: 0, expected: token });
+    }
+}